@@ -1,23 +1,63 @@
+mod annealing;
+mod beam;
+mod cmaes;
+pub mod config;
+pub mod constraints;
+mod diffevo;
+mod distance;
+pub mod fitness;
 mod geneticalg;
+mod greedy;
+mod islands;
+mod optimizer;
+pub mod pareto;
+pub mod progress;
+mod refine;
+pub mod savestate;
+mod segments;
+mod shorten;
+mod simcache;
 mod waterspeed;
 
 // use std::time::{Duration, Instant};
-use std::num::ParseFloatError;
+use std::num::{ParseFloatError, ParseIntError};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
-use geneticalg::{Inputs, InputsBuilder, InputsPop, Simulator};
+use annealing::SimulatedAnnealing;
+use beam::BeamSearch;
+use cmaes::CmaEs;
+use config::{ConfigError, GenomeKind, OptimizerKind, SolverConfig};
+use diffevo::DifferentialEvolution;
+use fitness::FitnessBreakdown;
+use geneticalg::{Genome, Inputs, InputsPop, OrdFloat64, Simulator};
+use greedy::Greedy;
+use optimizer::{seeded_rng, Optimizer};
+use progress::{RunContext, RunHandle, SolverEvent};
+use savestate::{RunInfo, SaveError, SolverState};
+use segments::SegmentsPop;
 
 use crate::colliders::Collider;
 use crate::colliders::Rect;
 use crate::level::Level;
-use crate::player::Player;
+use crate::player::{Player, DASH_OFFSET};
 use crate::point::Point;
 
 use arboard::Clipboard;
 use bitvec::prelude as bv;
 use colored::Colorize;
-use genevo::{operator::prelude::*, population::ValueEncodedGenomeBuilder, prelude::*};
+use genevo::random::{Prng, Seed, SeedableRng};
+use genevo::{operator::prelude::*, prelude::*};
 use thiserror::Error;
 
+/// Largest gene that is still a plain swim angle.
+const MAX_ANGLE: f64 = 359.99999999999994;
+/// Largest gene once dash presses are allowed.
+const MAX_DASH_GENE: f64 = DASH_OFFSET + MAX_ANGLE;
+
 #[derive(Error, Debug)]
 pub enum DataParseError {
     #[error("Invalid number of rectangle corners on line {0}: expected 4, got {1}")]
@@ -25,12 +65,27 @@ pub enum DataParseError {
 
     #[error("Invalid float literal")]
     InvalidFloat(#[from] ParseFloatError),
+
+    #[error("Invalid pickup index")]
+    InvalidPickupIndex(#[from] ParseIntError),
+
+    #[error("Pickup {0} doesn't exist: the level only has {1} pickups")]
+    PickupOutOfRange(usize, usize),
+
+    #[error("Invalid TAS input on line {0}: {1}")]
+    InvalidTasLine(usize, String),
+
+    #[error("There are no checkpoints or pickups to go for")]
+    NoCheckpoints,
 }
 
 fn parse_checkpoint(data: &str) -> Result<Vec<Rect>, DataParseError> {
     let data_split = data.split('\n').collect::<Vec<_>>();
     let mut rects = Vec::new();
     for (line, rect) in data_split.iter().enumerate() {
+        if rect.trim().is_empty() {
+            continue;
+        }
         let temp_split = rect.split(", ").collect::<Vec<_>>();
         if temp_split.len() != 4 {
             return Err(DataParseError::InvalidNumRectCorners(
@@ -50,70 +105,317 @@ fn parse_checkpoint(data: &str) -> Result<Vec<Rect>, DataParseError> {
     Ok(rects)
 }
 
+/// Turns a comma separated list of pickup indices into checkpoints, in the order given.
+fn parse_pickups(level: &Level, data: &str) -> Result<Vec<Rect>, DataParseError> {
+    data.split(',')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let index = x.parse::<usize>()?;
+            match level.pickups.get(index) {
+                Some(pickup) => Ok(pickup.hitbox()),
+                None => Err(DataParseError::PickupOutOfRange(index, level.pickups.len())),
+            }
+        })
+        .collect()
+}
+
+/// Every checkpoint the path has to hit, pickups first. There has to be at least one.
+fn parse_all_checkpoints(
+    level: &Level, checkpoints: &str, pickups: &str,
+) -> Result<Vec<Rect>, DataParseError> {
+    let mut all_checkpoints = parse_pickups(level, pickups)?;
+    all_checkpoints.extend(parse_checkpoint(checkpoints)?);
+    if all_checkpoints.is_empty() {
+        return Err(DataParseError::NoCheckpoints);
+    }
+    Ok(all_checkpoints)
+}
+
+/// How many frames the GA's genomes start out with.
+const INITIAL_FRAMES: usize = 5;
+
+/// Where the GA's first population comes from.
+enum Start {
+    Random,
+    Resume(Box<SolverState>),
+    /// Mutated copies of an existing input sequence.
+    From(Inputs),
+}
+
+/// What came out of running the GA for a while.
+struct Evolved<G> {
+    population: Vec<G>,
+    best: Inputs,
+}
+
+/// Runs up to `generations` generations of the GA on `population`. `extended` is whether the genomes
+/// have been extended yet, which switches to the later mutation rate. `on_generation` gets the
+/// generation, best genome and population after each one, and stops the GA early if it returns
+/// false.
 // NOTE: not handling the error here because of absurd error type
-fn initial_path(level: &Level, player: Player, checkpoints: Vec<Rect>) -> Inputs {
-    let initial_population = build_population()
-        .with_genome_builder(InputsBuilder)
-        .of_size(50) // TODO: allow for an option to change this please
-        .uniform_at_random();
-    let mut simulator = Simulator::new(player, level, checkpoints);
-    // TODO: put this in a loop
+fn evolve<'a, G: Genome>(
+    simulator: &Simulator<'a>, config: &SolverConfig, population: Vec<G>, extended: bool,
+    generations: u64, seed: Seed, mut on_generation: impl FnMut(u64, &Inputs, &[G]) -> bool,
+) -> Evolved<G>
+where
+    Simulator<'a>: FitnessFunction<G, OrdFloat64>,
+{
+    let mutation_rate = if extended {
+        config.mutation_rate
+    } else {
+        config.initial_mutation_rate
+    };
     let mut ga_sim = simulate(
         genetic_algorithm()
             .with_evaluation(simulator.clone())
-            .with_selection(MaximizeSelector::new(0.85, 12)) //  TODO: add options for this too
-            .with_crossover(SinglePointCrossBreeder::new())
-            .with_mutation(RandomValueMutator::new(0.02, 0f64, 359.99999999999994)) // TODO: ditto
-            .with_reinsertion(ElitistReinserter::new(simulator.clone(), false, 0.85)) // TODO: again
-            .with_initial_population(initial_population)
+            .with_selection(MaximizeSelector::new(
+                config.selection_ratio,
+                config.individuals_per_parents,
+            ))
+            .with_crossover(G::crossover(config))
+            .with_mutation(G::mutation(simulator, config, mutation_rate))
+            .with_reinsertion(ElitistReinserter::new(
+                simulator.clone(),
+                extended,
+                config.reinsertion_ratio,
+            ))
+            .with_initial_population(Population::with_individuals(population))
             .build(),
     )
-    .until(GenerationLimit::new(20)) // TODO: yet again
-    .build();
-    let mut frame_count: u32 = 5;
+    .until(GenerationLimit::new(generations))
+    .build_with_seed(seed);
     loop {
-        let result = loop {
-            let result = ga_sim.step();
-            match result {
-                // TODO: actually handle this stuff
-                Ok(SimResult::Intermediate(step)) => {
-                    println!("{}, {}", frame_count, step.iteration)
+        match ga_sim.step() {
+            Ok(SimResult::Intermediate(step)) => {
+                let best = step.result.best_solution.solution.genome.to_inputs().0;
+                let population = step.result.evaluated_population.individuals();
+                if !on_generation(step.iteration, &best, &population[..]) {
+                    break Evolved {
+                        population: population.to_vec(),
+                        best,
+                    };
                 }
-                Ok(SimResult::Final(step, processing_time, duration, stop_reason)) => {
-                    break step.result
+            }
+            Ok(SimResult::Final(step, _, _, _)) => {
+                break Evolved {
+                    population: (*step.result.evaluated_population.individuals()).clone(),
+                    best: step.result.best_solution.solution.genome.to_inputs().0,
                 }
-                Err(error) => panic!("{}", error),
             }
-        };
-        frame_count += 1;
-        let mut population = (*result.evaluated_population.individuals()).clone();
-        let hit_final = simulator.check_if_hit_final(&result.best_solution.solution.genome.0);
-        println!("{:?}", result.best_solution.solution.genome);
-        if hit_final {
+            Err(error) => panic!("{}", error),
+        }
+    }
+}
+
+/// How long the genomes should get next, going by how far the best path still has to go. The
+/// jumps get smaller as it gets closer, down to a frame at a time. Once the best path has hit a
+/// checkpoint it's capped at the estimate from where that happened, since a path that wandered off
+/// after it shouldn't make the genomes any longer.
+fn next_horizon(simulator: &Simulator, config: &SolverConfig, best: &Inputs) -> usize {
+    let (_, base_frame) = simulator.base();
+    let mut state = simulator.initial_state();
+    let mut cap = None;
+    for &gene in best {
+        let checkpoint_index = state.checkpoint_index;
+        if !simulator.step(&mut state, gene) {
+            break;
+        }
+        if state.checkpoint_index > checkpoint_index {
+            cap = Some(state.frame_count - base_frame + simulator.frames_left(&state));
+        }
+    }
+    let horizon = best.len()
+        + usize::max(
+            1,
+            (simulator.frames_left(&state) as f64 * config.horizon_jump).ceil() as usize,
+        );
+    cap.map_or(horizon, |cap| horizon.min(cap))
+}
+
+/// Holds a new random gene on the end of every genome until it's somewhere from a frame longer up
+/// to `horizon` frames long, so the population tries out a spread of lengths.
+fn extend_population<G: Genome>(population: &mut [G], horizon: usize, max_gene: f64, seed: Seed) {
+    let mut rng = Prng::from_seed(seed);
+    for p in population.iter_mut() {
+        let length = rng.gen_range(p.frames() + 1..=usize::max(horizon, p.frames() + 1));
+        let gene = rng.gen_range(0f64..=max_gene);
+        p.extend(length, gene);
+    }
+}
+
+/// Cuts `inputs` off right where they hit the final checkpoint, since jumping the horizon ahead
+/// can leave frames past it.
+fn trim_to_finish(simulator: &Simulator, mut inputs: Inputs) -> Inputs {
+    if let Some(frames) = simulator.frames_to_finish(&inputs) {
+        inputs.truncate(frames);
+    }
+    inputs
+}
+
+/// A population of copies of `inputs` with some genes swapped out at random, plus one untouched
+/// copy so the GA never ends up worse than what it started from. Dashes get dropped if they're off,
+/// and every genome is made to follow the angle constraints.
+fn population_from(inputs: &Inputs, config: &SolverConfig, seed: Seed) -> Vec<InputsPop> {
+    let max_gene = max_gene(config);
+    let mut rng = Prng::from_seed(seed);
+    let original = inputs
+        .iter()
+        .map(|&gene| {
+            if gene > max_gene {
+                gene - DASH_OFFSET
+            } else {
+                gene
+            }
+        })
+        .collect::<Inputs>();
+    (0..config.population_size)
+        .map(|i| {
+            let mut genome: Inputs = if i == 0 {
+                original.clone()
+            } else {
+                original
+                    .iter()
+                    .map(|&gene| {
+                        if rng.gen_bool(config.initial_mutation_rate) {
+                            rng.gen_range(0f64..=max_gene)
+                        } else {
+                            gene
+                        }
+                    })
+                    .collect()
+            };
+            config.angles.apply(&mut genome);
+            InputsPop(genome, Arc::new(Mutex::new(None)))
+        })
+        .collect()
+}
+
+/// The GA's first population when it isn't being resumed.
+fn first_population<G: Genome>(
+    config: &SolverConfig, inputs: Option<&Inputs>, seed: Seed,
+) -> Vec<G> {
+    match inputs {
+        Some(inputs) => population_from(inputs, config, seed)
+            .into_iter()
+            .map(G::from_inputs)
+            .collect(),
+        None => build_population()
+            .with_genome_builder(G::builder(config, INITIAL_FRAMES))
+            .of_size(config.population_size)
+            .using_seed(seed)
+            .individuals()
+            .to_vec(),
+    }
+}
+
+/// The GA on its own. It grows the genomes a few frames at a time until the best one finishes,
+/// saving its state after every generation.
+fn initial_path<'a, G: Genome>(
+    simulator: &Simulator<'a>, config: &SolverConfig, ctx: &RunContext, start: Start,
+) -> Inputs
+where
+    Simulator<'a>: FitnessFunction<G, OrdFloat64>,
+{
+    let max_gene = max_gene(config);
+    // every block of randomness gets its own seed from this, so the whole run follows from one seed
+    let (mut seeds, mut population, mut generation) = match start {
+        Start::Resume(state) => {
+            let population = state.population().into_iter().map(G::from_inputs).collect();
+            (state.rng.clone(), population, state.generation)
+        }
+        Start::Random => {
+            let mut seeds = seeded_rng(config);
+            let population = first_population(config, None, seeds.gen());
+            (seeds, population, 0)
+        }
+        Start::From(inputs) => {
+            let mut seeds = seeded_rng(config);
+            let population = first_population(config, Some(&inputs), seeds.gen());
+            (seeds, population, 0)
+        }
+    };
+    loop {
+        let extended = population.iter().any(|p: &G| p.frames() > INITIAL_FRAMES);
+        let start = generation;
+        let seed = seeds.gen();
+        let evolved = evolve(
+            simulator,
+            config,
+            population,
+            extended,
+            u64::max(config.generation_limit.saturating_sub(start), 1),
+            seed,
+            |generation, best, population| {
+                ctx.report(simulator, start + generation, best);
+                ctx.save(simulator, config, population, start + generation, &seeds);
+                !ctx.cancelled()
+            },
+        );
+        population = evolved.population;
+        if ctx.cancelled() {
+            break evolved.best;
+        }
+        if simulator.check_if_hit_final(&evolved.best) {
             // TODO: make breaking criteria more correct
-            break result.best_solution.solution.genome.0;
-        }
-        let to_add = build_population()
-            .with_genome_builder(ValueEncodedGenomeBuilder::new(1, 0f64, 359.99999999999994))
-            .of_size(population.len())
-            .uniform_at_random();
-        for (p, t) in population.iter_mut().zip(to_add.individuals().iter()) {
-            p.0.extend(t);
-            *p.1.lock().unwrap() = None;
-        }
-        ga_sim = simulate(
-            // TODO: all the options need to be checked here too
-            genetic_algorithm()
-                .with_evaluation(simulator.clone())
-                .with_selection(MaximizeSelector::new(0.85, 12))
-                .with_crossover(SinglePointCrossBreeder::new())
-                .with_mutation(RandomValueMutator::new(0.2, 0f64, 359.99999999999994))
-                .with_reinsertion(ElitistReinserter::new(simulator.clone(), true, 0.85))
-                .with_initial_population(Population::with_individuals(population))
-                .build(),
-        )
-        .until(GenerationLimit::new(20)) // TODO: yes, here too
-        .build();
+            break trim_to_finish(simulator, evolved.best);
+        }
+        let horizon = next_horizon(simulator, config, &evolved.best);
+        extend_population(&mut population, horizon, max_gene, seeds.gen());
+        generation = 0;
+        ctx.save(simulator, config, &population, generation, &seeds);
+    }
+}
+
+struct GeneticOptimizer {
+    config: SolverConfig,
+    start: Start,
+}
+
+impl Optimizer for GeneticOptimizer {
+    fn optimize(&mut self, simulator: &mut Simulator, ctx: &RunContext) -> Inputs {
+        let mut start = std::mem::replace(&mut self.start, Start::Random);
+        if self.config.greedy_seed && matches!(start, Start::Random) {
+            let greedy = greedy::greedy_path(simulator, &self.config, ctx);
+            // it couldn't take a single frame without dying, so there's nothing to start from
+            if !greedy.is_empty() {
+                start = Start::From(greedy);
+            }
+        }
+        if self.config.genome == GenomeKind::Segments {
+            initial_path::<SegmentsPop>(simulator, &self.config, ctx, start)
+        } else if self.config.islands > 1 {
+            let inputs = match &start {
+                Start::From(inputs) => Some(inputs),
+                _ => None,
+            };
+            islands::island_path(simulator, &self.config, ctx, inputs)
+        } else {
+            initial_path::<InputsPop>(simulator, &self.config, ctx, start)
+        }
+    }
+}
+
+/// Only the GA knows what to do with `start`, the others always start from scratch.
+fn optimizer_for(config: &SolverConfig, start: Start) -> Box<dyn Optimizer> {
+    match config.optimizer {
+        OptimizerKind::Genetic => Box::new(GeneticOptimizer {
+            config: config.clone(),
+            start,
+        }),
+        OptimizerKind::CmaEs => Box::new(CmaEs::new(config)),
+        OptimizerKind::DifferentialEvolution => Box::new(DifferentialEvolution::new(config)),
+        OptimizerKind::SimulatedAnnealing => Box::new(SimulatedAnnealing::new(config)),
+        OptimizerKind::BeamSearch => Box::new(BeamSearch::new(config)),
+        OptimizerKind::Greedy => Box::new(Greedy::new(config)),
+    }
+}
+
+fn max_gene(config: &SolverConfig) -> f64 {
+    if config.allow_dashes {
+        MAX_DASH_GENE
+    } else {
+        MAX_ANGLE
     }
 }
 
@@ -121,38 +423,242 @@ fn initial_path(level: &Level, player: Player, checkpoints: Vec<Rect>) -> Inputs
 pub enum AlgorithmError {
     #[error(transparent)]
     DataParseError(#[from] DataParseError),
+
+    #[error(transparent)]
+    ConfigError(#[from] ConfigError),
+
+    #[error(transparent)]
+    SaveError(#[from] SaveError),
+
+    #[error("The solver crashed: {0}")]
+    Panicked(String),
 }
 
-pub fn run_alg(level: Level, player: Player, checkpoints: &str) -> Result<(), AlgorithmError> {
-    let base_inputs = initial_path(&level, player, parse_checkpoint(checkpoints)?);
-    // TODO: the rest of the damn thing
+/// `tas` is CelesteTAS input for the GA to start from and improve on. Leave it empty to start
+/// from random inputs.
+pub fn run_alg(
+    level: Level, player: Player, checkpoints: &str, pickups: &str, tas: &str,
+    config: &SolverConfig, ctx: &RunContext,
+) -> Result<FitnessBreakdown, AlgorithmError> {
+    let start = match parse_tas(tas)? {
+        inputs if inputs.is_empty() => Start::Random,
+        inputs => Start::From(inputs),
+    };
+    solve(level, player, checkpoints, pickups, config, start, ctx)
+}
+
+/// Picks a GA run back up from a saved state, using the player, checkpoints and config it was
+/// started with. `level` should come from the state's `info_path`, and it won't start if that's
+/// been written over since. Even with a seed, it won't turn out exactly like a run that never
+/// stopped.
+pub fn resume_alg(
+    level: Level, state: SolverState, ctx: &RunContext,
+) -> Result<FitnessBreakdown, AlgorithmError> {
+    state.check_level()?;
+    let (player, checkpoints, pickups, config) = (
+        state.player.clone(),
+        state.checkpoints.clone(),
+        state.pickups.clone(),
+        state.config.clone(),
+    );
+    solve(
+        level,
+        player,
+        &checkpoints,
+        &pickups,
+        &config,
+        Start::Resume(Box::new(state)),
+        ctx,
+    )
+}
+
+fn solve(
+    level: Level, player: Player, checkpoints: &str, pickups: &str, config: &SolverConfig,
+    start: Start, ctx: &RunContext,
+) -> Result<FitnessBreakdown, AlgorithmError> {
+    config.validate()?;
+    if ctx.saving() || matches!(start, Start::Resume(_)) {
+        config.check_saveable()?;
+    }
+    let seed = config.seed.unwrap_or_else(|| seeded_rng(config).gen());
+    println!("Seed: {seed}");
+    let config = &SolverConfig {
+        seed: Some(seed),
+        ..config.clone()
+    };
+    let all_checkpoints = parse_all_checkpoints(&level, checkpoints, pickups)?;
+    let mut simulator = Simulator::new(player, &level, all_checkpoints)
+        .with_weights(config.fitness.clone())
+        .with_exit(config.exit.clone())
+        .with_angles(config.angles.clone())
+        .with_cache(config.cache_interval, config.cache_size);
+    if let Start::Resume(state) = &start {
+        simulator = simulator.with_base(state.base_checkpoint, state.base_frame);
+    }
+    let mut base_inputs = optimizer_for(config, start).optimize(&mut simulator, ctx);
+    if config.shorten && !ctx.cancelled() {
+        let before = base_inputs.len();
+        base_inputs = shorten::shorten(&simulator, base_inputs, config, ctx);
+        ctx.send(SolverEvent::Shortened(
+            before.saturating_sub(base_inputs.len()),
+        ));
+    }
+    if config.refine && !ctx.cancelled() {
+        base_inputs = refine::refine(&simulator, base_inputs, config, ctx);
+    }
+    if config.pareto && !ctx.cancelled() && simulator.check_if_hit_final(&base_inputs) {
+        let front = pareto::pareto_front(&simulator, config, &base_inputs, ctx);
+        println!("{} paths on the Pareto front", front.len());
+        // the fewest frames one goes to the clipboard, the rest are up to the user
+        if let Some(fastest) = front.into_iter().next() {
+            base_inputs = fastest.genome;
+        }
+    }
+    simulator.constrain(&mut base_inputs);
+    let breakdown = simulator.fitness_breakdown(&base_inputs);
     let out = format_inputs(base_inputs);
     println!("{out}");
     let mut clipboard = Clipboard::new().unwrap();
     clipboard.set_text(out).unwrap();
-    Ok(())
+    Ok(breakdown)
+}
+
+/// Runs `run` on its own thread so whoever started it can keep going, sending progress back
+/// as it goes and a [`SolverEvent::Finished`] at the end.
+fn spawn(
+    save: Option<(PathBuf, RunInfo)>,
+    run: impl FnOnce(&RunContext) -> Result<FitnessBreakdown, AlgorithmError> + Send + 'static,
+) -> RunHandle {
+    let (sender, receiver) = mpsc::channel();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let mut ctx = RunContext::new(sender, cancel.clone());
+    if let Some((path, info)) = save {
+        ctx = ctx.with_save(path, info);
+    }
+    thread::spawn(move || {
+        // anything that panics still has to tell the GUI it's done, or it waits forever
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run(&ctx))).unwrap_or_else(|e| {
+            let message = match (e.downcast_ref::<&str>(), e.downcast_ref::<String>()) {
+                (Some(message), _) => message.to_string(),
+                (_, Some(message)) => message.clone(),
+                _ => String::from("unknown panic"),
+            };
+            Err(AlgorithmError::Panicked(message))
+        });
+        ctx.send(SolverEvent::Finished(result));
+    });
+    RunHandle::new(receiver, cancel)
+}
+
+/// [`run_alg`] in the background, saving the GA's state to `save_path` as it goes if there is one.
+pub fn spawn_run(
+    level: Level, player: Player, info: RunInfo, tas: String, config: SolverConfig,
+    save_path: Option<PathBuf>,
+) -> RunHandle {
+    let (checkpoints, pickups) = (info.checkpoints.clone(), info.pickups.clone());
+    spawn(save_path.map(|path| (path, info)), move |ctx| {
+        run_alg(level, player, &checkpoints, &pickups, &tas, &config, ctx)
+    })
+}
+
+/// [`resume_alg`] in the background, saving the GA's state to `save_path` as it goes if there is one.
+pub fn spawn_resume(level: Level, state: SolverState, save_path: Option<PathBuf>) -> RunHandle {
+    let save = save_path.map(|path| {
+        let info = RunInfo {
+            info_path: state.info_path.clone(),
+            level_hash: state.level_hash,
+            checkpoints: state.checkpoints.clone(),
+            pickups: state.pickups.clone(),
+        };
+        (path, info)
+    });
+    spawn(save, move |ctx| resume_alg(level, state, ctx))
+}
+
+fn format_line(count: usize, gene: f64) -> String {
+    if gene >= DASH_OFFSET {
+        format!("{count},x,f,{}\n", gene - DASH_OFFSET)
+    } else {
+        format!("{count},f,{gene}\n")
+    }
 }
 
 fn format_inputs(inp: Inputs) -> String {
+    let Some(&first) = inp.first() else {
+        return String::new();
+    };
     let mut count = 1;
-    let mut current = inp[0];
+    let mut current = first;
     let mut out = "".to_owned();
     for i in inp[1..].iter() {
         if *i == current {
             count += 1;
         } else {
-            out += &format!("{count},f,{current}\n");
+            out += &format_line(count, current);
             count = 1;
             current = *i;
         }
     }
-    out += &format!("{count},f,{current}\n");
+    out += &format_line(count, current);
     out
 }
 
+/// Reads CelesteTAS input lines back into genes, the reverse of [`format_inputs`]. Lines without a
+/// feather angle aim wherever their direction keys point. Lines with neither get rejected, since
+/// letting go underwater slows the player down and there's no gene that does that. Anything that
+/// isn't an input line, like comments and commands, gets skipped.
+fn parse_tas(data: &str) -> Result<Inputs, DataParseError> {
+    let mut inputs = Inputs::new();
+    for (line, text) in data.lines().enumerate() {
+        let mut actions = text.split(',').map(str::trim);
+        let Some(Ok(frames)) = actions.next().map(str::parse::<usize>) else {
+            continue;
+        };
+        let invalid = || DataParseError::InvalidTasLine(line, text.to_owned());
+        let (mut dash, mut feather, mut x, mut y) = (false, None, 0f64, 0f64);
+        while let Some(action) = actions.next() {
+            match action.to_ascii_uppercase().as_str() {
+                "F" => {
+                    // an empty angle is a neutral feather input
+                    feather = match actions.next() {
+                        Some("") => None,
+                        Some(value) => Some(value.parse::<f64>().map_err(|_| invalid())?),
+                        None => return Err(invalid()),
+                    };
+                }
+                "X" | "C" | "Z" => dash = true,
+                "R" => x += 1f64,
+                "L" => x -= 1f64,
+                "U" => y += 1f64,
+                "D" => y -= 1f64,
+                // jumps, grabs, and the feather's magnitude don't mean anything underwater
+                _ => {}
+            }
+        }
+        let angle = match feather {
+            Some(feather) => feather.rem_euclid(360f64),
+            None if x != 0f64 || y != 0f64 => x.atan2(y).to_degrees().rem_euclid(360f64),
+            None => return Err(invalid()),
+        };
+        let gene = if dash { angle + DASH_OFFSET } else { angle };
+        inputs.extend(std::iter::repeat_n(gene, frames));
+    }
+    Ok(inputs)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::algorithm::format_inputs;
+    use crate::algorithm::config::SolverConfig;
+    use crate::algorithm::geneticalg::{InputsPop, Simulator};
+    use crate::algorithm::progress::RunContext;
+    use crate::algorithm::{
+        format_inputs, initial_path, next_horizon, parse_all_checkpoints, parse_pickups, parse_tas,
+        spawn, AlgorithmError, DataParseError, SolverEvent, Start,
+    };
+    use crate::colliders::Rect;
+    use crate::entities::{Pickup, PickupKind};
+    use crate::level::Level;
+    use crate::point::Point;
 
     #[test]
     fn format_inputs_test() {
@@ -160,4 +666,117 @@ mod tests {
         let got = format_inputs(vec![4.2, 99.3, 99.3, 99.3, 99.3, 99.3, 55.9, 55.9]);
         assert_eq!(expected, got);
     }
+
+    #[test]
+    fn format_inputs_dash_test() {
+        let expected = format!("2,f,{}\n1,x,f,{}\n", 4.2f64, 45f64);
+        let got = format_inputs(vec![4.2, 4.2, 405.0]);
+        assert_eq!(expected, got);
+    }
+
+    #[test]
+    fn parse_tas_test() {
+        let inputs = vec![4.2, 99.3, 99.3, 405.0, 55.9, 55.9];
+        let Ok(got) = parse_tas(&format_inputs(inputs.clone())) else {
+            panic!("formatted inputs failed to parse");
+        };
+        assert_eq!(got, inputs);
+        let Ok(got) = parse_tas("# room start\n   2,R,J\nRead,other.tas\n1,X,L,U\n") else {
+            panic!("valid input lines failed to parse");
+        };
+        assert_eq!(got, vec![90.0, 90.0, 675.0]);
+        assert!(matches!(
+            parse_tas("3,F,up"),
+            Err(DataParseError::InvalidTasLine(0, _))
+        ));
+        // neutral underwater slows down, which no gene can do
+        assert!(matches!(
+            parse_tas("2,R\n1,J"),
+            Err(DataParseError::InvalidTasLine(1, _))
+        ));
+        assert!(matches!(
+            parse_tas("1,F,"),
+            Err(DataParseError::InvalidTasLine(0, _))
+        ));
+    }
+
+    #[test]
+    fn parse_pickups_test() {
+        let mut level = Level::default();
+        level.pickups = vec![
+            Pickup::new(PickupKind::Strawberry, Point::new(0f32, 0f32)),
+            Pickup::new(PickupKind::Key, Point::new(100f32, 50f32)),
+        ];
+        let Ok(got) = parse_pickups(&level, "1, 0") else {
+            panic!("valid pickup indices failed to parse");
+        };
+        assert_eq!(got[0].ul, Point::new(94f32, 44f32));
+        assert_eq!(got[1].ul, Point::new(-7f32, -7f32));
+        assert!(matches!(
+            parse_pickups(&level, "2"),
+            Err(DataParseError::PickupOutOfRange(2, 2))
+        ));
+    }
+
+    #[test]
+    fn no_checkpoints_test() {
+        let level = Level::default();
+        assert!(matches!(
+            parse_all_checkpoints(&level, " \n\n", ""),
+            Err(DataParseError::NoCheckpoints)
+        ));
+        let Ok(got) = parse_all_checkpoints(&level, "\n10, -16, 14, 16\n", "") else {
+            panic!("a single checkpoint failed to parse");
+        };
+        assert_eq!(got.len(), 1);
+    }
+
+    #[test]
+    fn panicking_run_test() {
+        let handle = spawn(None, |_| panic!("out of checkpoints"));
+        match handle.events.recv() {
+            Ok(SolverEvent::Finished(Err(AlgorithmError::Panicked(message)))) => {
+                assert_eq!(message, "out of checkpoints");
+            }
+            other => panic!("expected a crash, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn seeded_run_test() {
+        let (level, player, checkpoint) = Level::test_course();
+        let config = SolverConfig {
+            generation_limit: 5,
+            seed: Some(1234),
+            ..Default::default()
+        };
+        let run = || {
+            let checkpoints = vec![checkpoint];
+            initial_path::<InputsPop>(
+                &Simulator::new(player.clone(), &level, checkpoints),
+                &config,
+                &RunContext::default(),
+                Start::Random,
+            )
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn horizon_test() {
+        let (level, player, checkpoint) = Level::test_course();
+        let checkpoints = vec![checkpoint, Rect::new_xywh(40f32, -16f32, 4f32, 32f32)];
+        let simulator = Simulator::new(player, &level, checkpoints);
+        let config = SolverConfig::default();
+        // still on the way to the first checkpoint, so it jumps ahead
+        let short = vec![90f64; 5];
+        assert!(next_horizon(&simulator, &config, &short) > short.len() + 1);
+        // hits the first checkpoint, then swims back the way it came
+        let mut wandering = vec![90f64; 30];
+        wandering.extend([270f64; 80]);
+        assert_eq!(simulator.sim_player(&wandering).checkpoint_index, 1);
+        let horizon = next_horizon(&simulator, &config, &wandering);
+        assert!(horizon < wandering.len());
+        assert!(horizon > 30);
+    }
 }
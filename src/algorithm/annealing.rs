@@ -0,0 +1,76 @@
+use genevo::random::{Prng, Rng};
+
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, Simulator};
+use super::optimizer::{
+    gaussian, gene_range, grow_horizon, seeded_rng, wrap_gene, wrap_genes, Optimizer,
+};
+use super::progress::RunContext;
+
+/// How far the temperature falls over a search, as a fraction of where it started.
+const FINAL_TEMPERATURE: f64 = 0.001;
+
+/// Nudges one frame at a time, cooling geometrically over `generation_limit * population_size` moves
+/// so it gets the same number of evaluations as the population based optimizers.
+pub(super) struct SimulatedAnnealing {
+    config: SolverConfig,
+    rng: Prng,
+}
+
+impl SimulatedAnnealing {
+    pub fn new(config: &SolverConfig) -> Self {
+        Self {
+            config: config.clone(),
+            rng: seeded_rng(config),
+        }
+    }
+
+    fn search(&mut self, simulator: &Simulator, ctx: &RunContext, start: &Inputs) -> Inputs {
+        let range = gene_range(&self.config);
+        let iterations = self.config.generation_limit * self.config.population_size as u64;
+        let mut current = wrap_genes(start, range);
+        let mut current_fitness = simulator.fitness(&current);
+        let mut best = current.clone();
+        let mut best_fitness = current_fitness;
+        for k in 0..iterations {
+            // one generation's worth of moves
+            if k % self.config.population_size as u64 == 0 {
+                if ctx.cancelled() {
+                    break;
+                }
+                ctx.report(simulator, k / self.config.population_size as u64, &best);
+            }
+            let temperature = self.config.annealing_temperature
+                * FINAL_TEMPERATURE.powf(k as f64 / iterations as f64);
+            let mut candidate = current.clone();
+            let frame = self.rng.gen_range(0..candidate.len());
+            candidate[frame] = wrap_gene(
+                candidate[frame] + gaussian(&mut self.rng) * self.config.annealing_step,
+                range,
+            );
+            let fitness = simulator.fitness(&candidate);
+            if fitness >= current_fitness
+                || self.rng.gen::<f64>() < ((fitness - current_fitness) / temperature).exp()
+            {
+                current = candidate;
+                current_fitness = fitness;
+                if current_fitness > best_fitness {
+                    best = current.clone();
+                    best_fitness = current_fitness;
+                }
+            }
+        }
+        best
+    }
+}
+
+impl Optimizer for SimulatedAnnealing {
+    fn optimize(&mut self, simulator: &mut Simulator, ctx: &RunContext) -> Inputs {
+        grow_horizon(simulator, ctx, self.config.search_max_frames, |start| {
+            self.search(simulator, ctx, start)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, SimState, Simulator};
+use super::optimizer::Optimizer;
+use super::progress::RunContext;
+use crate::player::DASH_OFFSET;
+use crate::point::Point;
+
+/// Steps every kept state forward by each of a fixed set of angles a frame at a time, only keeping
+/// the best `beam_width` states. There's no randomness, so the same level always gives the same path.
+pub(super) struct BeamSearch {
+    config: SolverConfig,
+}
+
+/// Which gene got a state here, and which state in the previous frame's beam it came from.
+#[derive(Clone, Copy)]
+struct Step {
+    parent: usize,
+    gene: f64,
+}
+
+impl BeamSearch {
+    pub fn new(config: &SolverConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    fn genes(&self) -> Vec<f64> {
+        let n = self.config.beam_angles;
+        let angles = (0..n).map(|i| DASH_OFFSET * i as f64 / n as f64);
+        if self.config.allow_dashes {
+            angles
+                .clone()
+                .chain(angles.map(|a| a + DASH_OFFSET))
+                .collect()
+        } else {
+            angles.collect()
+        }
+    }
+
+    /// Follows the parents back from `index` in the last frame to get the inputs that lead to it.
+    fn trace(history: &[Vec<Step>], mut index: usize) -> Inputs {
+        let mut inputs = history
+            .iter()
+            .rev()
+            .map(|frame| {
+                let step = frame[index];
+                index = step.parent;
+                step.gene
+            })
+            .collect::<Inputs>();
+        inputs.reverse();
+        inputs
+    }
+}
+
+/// Removes every state where there's another one in the same pixel heading to the same checkpoint
+/// that's at least as far along with at least as much speed towards it.
+fn prune_dominated(
+    simulator: &Simulator, candidates: Vec<(Step, SimState)>,
+) -> Vec<(Step, SimState)> {
+    let checkpoints = simulator.checkpoints();
+    let measured = candidates
+        .iter()
+        .map(|(_, state)| {
+            let pos = state.player.pos();
+            let target = checkpoints[state.checkpoint_index].center();
+            let towards = target - pos;
+            // right on the centre there's no way to go, so every direction counts the same
+            let dir = if towards.magnitude() > 0f32 {
+                towards.normalize()
+            } else {
+                Point::new(0f32, 0f32)
+            };
+            (pos.dot(dir), state.player.speed.dot(dir))
+        })
+        .collect::<Vec<_>>();
+    let mut buckets: HashMap<_, Vec<usize>> = HashMap::new();
+    for (i, (_, state)) in candidates.iter().enumerate() {
+        let pos = state.player.pos().round();
+        let key = (
+            state.checkpoint_index,
+            pos.x as i32,
+            pos.y as i32,
+            state.player.dashes,
+            state.player.dashing(),
+        );
+        buckets.entry(key).or_default().push(i);
+    }
+    let mut keep = vec![true; candidates.len()];
+    for bucket in buckets.values() {
+        for &i in bucket {
+            let (progress, speed) = measured[i];
+            keep[i] = !bucket.iter().any(|&j| {
+                let (other_progress, other_speed) = measured[j];
+                j != i
+                    && other_progress >= progress
+                    && other_speed >= speed
+                    // exact ties keep whichever came first
+                    && (other_progress > progress || other_speed > speed || j < i)
+            });
+        }
+    }
+    candidates
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(candidate, keep)| keep.then_some(candidate))
+        .collect()
+}
+
+impl Optimizer for BeamSearch {
+    fn optimize(&mut self, simulator: &mut Simulator, ctx: &RunContext) -> Inputs {
+        let simulator = &*simulator;
+        let genes = self.genes();
+        let mut beam = vec![simulator.initial_state()];
+        let mut history: Vec<Vec<Step>> = Vec::new();
+        for frame in 0..self.config.beam_max_frames {
+            if ctx.cancelled() {
+                break;
+            }
+            let candidates = beam
+                .par_iter()
+                .enumerate()
+                .flat_map_iter(|(parent, state)| {
+                    genes.iter().filter_map(move |&gene| {
+                        let mut next = state.clone();
+                        simulator.step(&mut next, gene);
+                        next.player.alive.then_some((Step { parent, gene }, next))
+                    })
+                })
+                .collect::<Vec<_>>();
+            if candidates.is_empty() {
+                break;
+            }
+            if let Some((step, _)) = candidates
+                .iter()
+                .filter(|(_, state)| simulator.hit_final(state))
+                .max_by(|(_, a), (_, b)| simulator.score(a).total_cmp(&simulator.score(b)))
+            {
+                // every finished path is the same length, so the first frame one shows up is the best
+                history.push(vec![*step]);
+                return Self::trace(&history, 0);
+            }
+            // anything else that got through every checkpoint broke a hard exit constraint, and
+            // there's nothing left for it to hit
+            let candidates = candidates
+                .into_iter()
+                .filter(|(_, state)| !simulator.reached_final(state))
+                .collect::<Vec<_>>();
+            if candidates.is_empty() {
+                break;
+            }
+            let candidates = prune_dominated(simulator, candidates);
+            let mut scored = candidates
+                .into_par_iter()
+                .map(|(step, state)| (simulator.score(&state), step, state))
+                .collect::<Vec<_>>();
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+            scored.truncate(self.config.beam_width);
+            let (steps, states) = scored
+                .into_iter()
+                .map(|(_, step, state)| (step, state))
+                .unzip();
+            history.push(steps);
+            beam = states;
+            ctx.report(simulator, frame as u64, &Self::trace(&history, 0));
+        }
+        // never got there or got cancelled, so hand back whatever got the closest
+        if history.is_empty() {
+            Inputs::new()
+        } else {
+            Self::trace(&history, 0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::fitness::ExitConstraints;
+    use crate::level::Level;
+
+    #[test]
+    fn hard_exit_beam_test() {
+        let (level, player, checkpoint) = Level::test_course();
+        let checkpoints = vec![checkpoint];
+        let config = SolverConfig {
+            beam_width: 20,
+            beam_angles: 8,
+            beam_max_frames: 30,
+            ..Default::default()
+        };
+        let exit = ExitConstraints {
+            min_speed_x: Some(10_000f64),
+            ..Default::default()
+        };
+        let soft =
+            Simulator::new(player.clone(), &level, checkpoints.clone()).with_exit(exit.clone());
+        let path = BeamSearch::new(&config).optimize(&mut soft.clone(), &RunContext::default());
+        assert!(soft.frames_to_finish(&path).is_some());
+
+        // no way to leave fast enough, so every path that gets there has to be thrown away
+        let mut hard = Simulator::new(player, &level, checkpoints)
+            .with_exit(ExitConstraints { hard: true, ..exit });
+        let path = BeamSearch::new(&config).optimize(&mut hard, &RunContext::default());
+        assert!(hard.frames_to_finish(&path).is_none());
+    }
+}
@@ -0,0 +1,138 @@
+use genevo::random::Prng;
+use rayon::prelude::*;
+
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, Simulator};
+use super::optimizer::{gaussian, gene_range, grow_horizon, seeded_rng, wrap_genes, Optimizer};
+use super::progress::RunContext;
+
+/// Separable CMA-ES. It only learns the diagonal of the covariance matrix, which keeps every
+/// generation linear in the number of frames while still adapting the step size per frame.
+pub(super) struct CmaEs {
+    config: SolverConfig,
+    rng: Prng,
+}
+
+impl CmaEs {
+    pub fn new(config: &SolverConfig) -> Self {
+        Self {
+            config: config.clone(),
+            rng: seeded_rng(config),
+        }
+    }
+
+    fn search(&mut self, simulator: &Simulator, ctx: &RunContext, start: &Inputs) -> Inputs {
+        let range = gene_range(&self.config);
+        let n = start.len();
+        let nf = n as f64;
+        let lambda = self.config.population_size;
+        let mu = lambda / 2;
+        let raw_weights = (0..mu)
+            .map(|i| (mu as f64 + 0.5).ln() - ((i + 1) as f64).ln())
+            .collect::<Vec<_>>();
+        let weight_sum = raw_weights.iter().sum::<f64>();
+        let weights = raw_weights
+            .iter()
+            .map(|w| w / weight_sum)
+            .collect::<Vec<_>>();
+        let mu_eff = 1f64 / weights.iter().map(|w| w * w).sum::<f64>();
+
+        let c_sigma = (mu_eff + 2f64) / (nf + mu_eff + 5f64);
+        let d_sigma =
+            1f64 + 2f64 * f64::max(0f64, ((mu_eff - 1f64) / (nf + 1f64)).sqrt() - 1f64) + c_sigma;
+        let c_c = (4f64 + mu_eff / nf) / (nf + 4f64 + 2f64 * mu_eff / nf);
+        // only learning the diagonal means it can afford to learn faster
+        let sep = (nf + 2f64) / 3f64;
+        let c_1 = f64::min(1f64, sep * 2f64 / ((nf + 1.3f64).powi(2) + mu_eff));
+        let c_mu = f64::min(
+            1f64 - c_1,
+            sep * 2f64 * (mu_eff - 2f64 + 1f64 / mu_eff) / ((nf + 2f64).powi(2) + mu_eff),
+        );
+        let chi_n = nf.sqrt() * (1f64 - 1f64 / (4f64 * nf) + 1f64 / (21f64 * nf * nf));
+
+        let mut mean = start.clone();
+        let mut sigma = self.config.cmaes_sigma;
+        let mut variances = vec![1f64; n];
+        let mut p_sigma = vec![0f64; n];
+        let mut p_c = vec![0f64; n];
+        let mut best = wrap_genes(start, range);
+        let mut best_fitness = simulator.fitness(&best);
+        for generation in 0..self.config.generation_limit {
+            if ctx.cancelled() {
+                break;
+            }
+            let samples = (0..lambda)
+                .map(|_| {
+                    (0..n)
+                        .map(|j| mean[j] + sigma * variances[j].sqrt() * gaussian(&mut self.rng))
+                        .collect::<Inputs>()
+                })
+                .collect::<Vec<_>>();
+            let fitnesses = samples
+                .par_iter()
+                .map(|x| simulator.fitness(&wrap_genes(x, range)))
+                .collect::<Vec<_>>();
+            let mut order = (0..lambda).collect::<Vec<_>>();
+            order.sort_by(|&a, &b| fitnesses[b].total_cmp(&fitnesses[a]));
+            if fitnesses[order[0]] > best_fitness {
+                best_fitness = fitnesses[order[0]];
+                best = wrap_genes(&samples[order[0]], range);
+            }
+            ctx.report(simulator, generation, &best);
+
+            let old_mean = mean.clone();
+            for (j, m) in mean.iter_mut().enumerate() {
+                *m = weights
+                    .iter()
+                    .zip(order.iter())
+                    .map(|(w, &k)| w * samples[k][j])
+                    .sum();
+            }
+            let y_w = (0..n)
+                .map(|j| (mean[j] - old_mean[j]) / sigma)
+                .collect::<Vec<_>>();
+            let sigma_scale = (c_sigma * (2f64 - c_sigma) * mu_eff).sqrt();
+            for j in 0..n {
+                p_sigma[j] =
+                    (1f64 - c_sigma) * p_sigma[j] + sigma_scale * y_w[j] / variances[j].sqrt();
+            }
+            let p_sigma_norm = p_sigma.iter().map(|p| p * p).sum::<f64>().sqrt();
+            let decay = (1f64 - c_sigma).powi(2 * (generation as i32).saturating_add(1));
+            let h_sigma =
+                if p_sigma_norm / (1f64 - decay).sqrt() < (1.4f64 + 2f64 / (nf + 1f64)) * chi_n {
+                    1f64
+                } else {
+                    0f64
+                };
+            let c_scale = (c_c * (2f64 - c_c) * mu_eff).sqrt();
+            for j in 0..n {
+                p_c[j] = (1f64 - c_c) * p_c[j] + h_sigma * c_scale * y_w[j];
+                let rank_mu = weights
+                    .iter()
+                    .zip(order.iter())
+                    .map(|(w, &k)| w * ((samples[k][j] - old_mean[j]) / sigma).powi(2))
+                    .sum::<f64>();
+                variances[j] = (1f64 - c_1 - c_mu) * variances[j]
+                    + c_1 * (p_c[j].powi(2) + (1f64 - h_sigma) * c_c * (2f64 - c_c) * variances[j])
+                    + c_mu * rank_mu;
+            }
+            // anything past half the range is just as random as sampling uniformly
+            sigma = f64::min(
+                sigma * ((c_sigma / d_sigma) * (p_sigma_norm / chi_n - 1f64)).exp(),
+                range / 2f64,
+            );
+        }
+        best
+    }
+}
+
+impl Optimizer for CmaEs {
+    fn optimize(&mut self, simulator: &mut Simulator, ctx: &RunContext) -> Inputs {
+        grow_horizon(simulator, ctx, self.config.search_max_frames, |start| {
+            self.search(simulator, ctx, start)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {}
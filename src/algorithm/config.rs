@@ -0,0 +1,475 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::constraints::AngleConstraints;
+use super::fitness::{ExitConstraints, FitnessWeights};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Couldn't read the config file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Couldn't parse the config file: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("Population size must be at least {1}, got {0}")]
+    PopulationTooSmall(usize, usize),
+
+    #[error("Individuals per parents must be at least 2, got {0}")]
+    TooFewParents(usize),
+
+    #[error("{0} must be between 0 and 1, got {1}")]
+    OutOfUnitRange(&'static str, f64),
+
+    #[error("Generation limit must be at least 1")]
+    NoGenerations,
+
+    #[error("{0} must be above 0, got {1}")]
+    NotPositive(&'static str, f64),
+
+    #[error("Differential weight must be between 0 and 2, got {0}")]
+    InvalidDifferentialWeight(f64),
+
+    #[error("{0} can't be negative, got {1}")]
+    Negative(&'static str, f64),
+
+    #[error("Can't migrate {0} individuals out of a population of {1}")]
+    TooManyMigrants(usize, usize),
+
+    #[error("{0} must be at least 1")]
+    Zero(&'static str),
+
+    #[error("Islands only work with the per-frame genome")]
+    IslandsNeedPerFrame,
+
+    #[error("{0} goes from {1} down to {2}")]
+    BackwardsRange(&'static str, f64, f64),
+
+    #[error("{0} can't save or resume its state")]
+    CantSave(&'static str),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizerKind {
+    #[default]
+    Genetic,
+    CmaEs,
+    DifferentialEvolution,
+    SimulatedAnnealing,
+    BeamSearch,
+    Greedy,
+}
+
+impl OptimizerKind {
+    pub const ALL: [OptimizerKind; 6] = [
+        OptimizerKind::Genetic,
+        OptimizerKind::CmaEs,
+        OptimizerKind::DifferentialEvolution,
+        OptimizerKind::SimulatedAnnealing,
+        OptimizerKind::BeamSearch,
+        OptimizerKind::Greedy,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptimizerKind::Genetic => "Genetic algorithm",
+            OptimizerKind::CmaEs => "CMA-ES",
+            OptimizerKind::DifferentialEvolution => "Differential evolution",
+            OptimizerKind::SimulatedAnnealing => "Simulated annealing",
+            OptimizerKind::BeamSearch => "Beam search",
+            OptimizerKind::Greedy => "Greedy",
+        }
+    }
+}
+
+/// How the GA encodes a path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GenomeKind {
+    /// One angle per frame.
+    #[default]
+    PerFrame,
+    /// Runs of frames that all hold the same angle, like the lines of a TAS.
+    Segments,
+}
+
+impl GenomeKind {
+    pub const ALL: [GenomeKind; 2] = [GenomeKind::PerFrame, GenomeKind::Segments];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GenomeKind::PerFrame => "Angle per frame",
+            GenomeKind::Segments => "Segments",
+        }
+    }
+}
+
+/// Which islands send their best individuals to which when running the GA on several islands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationTopology {
+    /// Each island only sends to the next one along.
+    #[default]
+    Ring,
+    /// Each island gets the best of everyone else's.
+    FullyConnected,
+}
+
+impl MigrationTopology {
+    pub const ALL: [MigrationTopology; 2] =
+        [MigrationTopology::Ring, MigrationTopology::FullyConnected];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MigrationTopology::Ring => "Ring",
+            MigrationTopology::FullyConnected => "Fully connected",
+        }
+    }
+}
+
+/// Everything that controls how the solver searches, so it can be tuned without recompiling.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SolverConfig {
+    pub population_size: usize,
+    pub selection_ratio: f64,
+    pub individuals_per_parents: usize,
+    /// Mutation rate for the very first batch of generations.
+    pub initial_mutation_rate: f64,
+    /// Mutation rate after each time the path gets extended.
+    pub mutation_rate: f64,
+    pub reinsertion_ratio: f64,
+    /// How many of the mutated genes get the swim model's angle towards the next checkpoint
+    /// rather than a random one.
+    pub swim_bias: f64,
+    /// How many generations to run before extending the path.
+    pub generation_limit: u64,
+    /// How much of the estimated frames still needed to add to the path each time it gets
+    /// extended. 0 always adds a single frame.
+    pub horizon_jump: f64,
+    pub allow_dashes: bool,
+    pub optimizer: OptimizerKind,
+    pub genome: GenomeKind,
+    /// How many populations the GA evolves at once. Anything above 1 turns on migration.
+    pub islands: usize,
+    /// How many generations the islands evolve on their own between migrations.
+    pub migration_interval: u64,
+    /// How many of its best individuals each island sends out per migration.
+    pub migrants: usize,
+    pub migration_topology: MigrationTopology,
+    /// Starting step size of CMA-ES, in degrees.
+    pub cmaes_sigma: f64,
+    pub de_differential_weight: f64,
+    pub de_crossover_rate: f64,
+    pub annealing_temperature: f64,
+    /// Standard deviation of each annealing move, in degrees.
+    pub annealing_step: f64,
+    /// CMA-ES, differential evolution and annealing give up once their paths get this long.
+    pub search_max_frames: usize,
+    /// How many states beam search keeps after each frame.
+    pub beam_width: usize,
+    /// How many evenly spaced angles beam search tries from each state.
+    pub beam_angles: usize,
+    /// Beam search gives up after this many frames.
+    pub beam_max_frames: usize,
+    /// The greedy solver gives up after this many frames.
+    pub greedy_max_frames: usize,
+    /// Whether the GA starts from copies of the greedy solver's path instead of random ones.
+    pub greedy_seed: bool,
+    /// Whether to try removing frames once the optimizer is done.
+    pub shorten: bool,
+    /// Whether to polish each frame's angle once the optimizer is done.
+    pub refine: bool,
+    /// How far either side of each angle the first refinement pass looks, in degrees.
+    pub refine_step: f64,
+    /// Whether to finish by trading frames for exit speed and subpixel, giving back a Pareto front
+    /// of paths to choose between.
+    pub pareto: bool,
+    pub pareto_generations: u64,
+    /// How many frames longer than the best path the paths on the front are allowed to be.
+    pub pareto_extra_frames: usize,
+    pub fitness: FitnessWeights,
+    pub exit: ExitConstraints,
+    pub angles: AngleConstraints,
+    /// How many frames apart the simulator saves states to pick genomes with the same start back
+    /// up from. 0 turns it off.
+    pub cache_interval: usize,
+    /// How many saved states to keep before throwing them all out.
+    pub cache_size: usize,
+    /// Fixes the randomness so runs can be reproduced. A random one gets picked when unset.
+    pub seed: Option<u64>,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            population_size: 50,
+            selection_ratio: 0.85,
+            individuals_per_parents: 12,
+            initial_mutation_rate: 0.02,
+            mutation_rate: 0.2,
+            reinsertion_ratio: 0.85,
+            swim_bias: 0.2,
+            generation_limit: 20,
+            horizon_jump: 0.5,
+            allow_dashes: false,
+            optimizer: OptimizerKind::Genetic,
+            genome: GenomeKind::PerFrame,
+            islands: 1,
+            migration_interval: 5,
+            migrants: 2,
+            migration_topology: MigrationTopology::Ring,
+            cmaes_sigma: 30f64,
+            de_differential_weight: 0.5,
+            de_crossover_rate: 0.9,
+            annealing_temperature: 100f64,
+            annealing_step: 15f64,
+            search_max_frames: 600,
+            beam_width: 200,
+            beam_angles: 36,
+            beam_max_frames: 600,
+            greedy_max_frames: 600,
+            greedy_seed: false,
+            shorten: true,
+            refine: true,
+            refine_step: 10f64,
+            pareto: false,
+            pareto_generations: 100,
+            pareto_extra_frames: 5,
+            fitness: FitnessWeights::default(),
+            exit: ExitConstraints::default(),
+            angles: AngleConstraints::default(),
+            cache_interval: 8,
+            cache_size: 50_000,
+            seed: None,
+        }
+    }
+}
+
+impl SolverConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(&std::fs::read_to_string(path)?)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.individuals_per_parents < 2 {
+            return Err(ConfigError::TooFewParents(self.individuals_per_parents));
+        }
+        // differential evolution needs 3 others to build each trial vector
+        let min_population = usize::max(self.individuals_per_parents, 4);
+        if self.population_size < min_population {
+            return Err(ConfigError::PopulationTooSmall(
+                self.population_size,
+                min_population,
+            ));
+        }
+        for (name, value) in [
+            ("Selection ratio", self.selection_ratio),
+            ("Initial mutation rate", self.initial_mutation_rate),
+            ("Mutation rate", self.mutation_rate),
+            ("Reinsertion ratio", self.reinsertion_ratio),
+            ("Swim bias", self.swim_bias),
+            ("Crossover rate", self.de_crossover_rate),
+        ] {
+            if !(0f64..=1f64).contains(&value) {
+                return Err(ConfigError::OutOfUnitRange(name, value));
+            }
+        }
+        if self.generation_limit == 0 {
+            return Err(ConfigError::NoGenerations);
+        }
+        for (name, value) in [
+            ("CMA-ES sigma", self.cmaes_sigma),
+            ("Annealing temperature", self.annealing_temperature),
+            ("Annealing step", self.annealing_step),
+            ("Refinement step", self.refine_step),
+        ] {
+            if value <= 0f64 || value.is_nan() {
+                return Err(ConfigError::NotPositive(name, value));
+            }
+        }
+        if !(self.de_differential_weight > 0f64 && self.de_differential_weight <= 2f64) {
+            return Err(ConfigError::InvalidDifferentialWeight(
+                self.de_differential_weight,
+            ));
+        }
+        let weights = &self.fitness;
+        for (name, value) in [
+            ("Checkpoint weight", weights.checkpoint),
+            ("Frames weight", weights.frames),
+            ("Unfinished frames weight", weights.unfinished_frames),
+            ("Closest approach weight", weights.closest_approach),
+            ("Closest frame weight", weights.closest_frame),
+            ("Final distance weight", weights.final_distance),
+            ("End speed weight", weights.end_speed),
+            ("Exit weight", weights.exit),
+            ("Exit direction tolerance", self.exit.direction_tolerance),
+            ("Horizon jump", self.horizon_jump),
+        ] {
+            if value < 0f64 || value.is_nan() {
+                return Err(ConfigError::Negative(name, value));
+            }
+        }
+        if self.migrants >= self.population_size {
+            return Err(ConfigError::TooManyMigrants(
+                self.migrants,
+                self.population_size,
+            ));
+        }
+        for (name, value) in [
+            ("Islands", self.islands),
+            ("Migration interval", self.migration_interval as usize),
+            ("Search max frames", self.search_max_frames),
+            ("Beam width", self.beam_width),
+            ("Beam angles", self.beam_angles),
+            ("Beam max frames", self.beam_max_frames),
+            ("Greedy max frames", self.greedy_max_frames),
+            ("Pareto generations", self.pareto_generations as usize),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::Zero(name));
+            }
+        }
+        let exit = &self.exit;
+        for (name, low, high) in [
+            ("Exit horizontal speed", exit.min_speed_x, exit.max_speed_x),
+            ("Exit vertical speed", exit.min_speed_y, exit.max_speed_y),
+        ] {
+            if let (Some(low), Some(high)) = (low, high) {
+                if low > high {
+                    return Err(ConfigError::BackwardsRange(name, low, high));
+                }
+            }
+        }
+        for (name, range) in [
+            ("Exit horizontal subpixel", exit.subpixel_x),
+            ("Exit vertical subpixel", exit.subpixel_y),
+        ] {
+            let Some([low, high]) = range else {
+                continue;
+            };
+            for value in [low, high] {
+                if !(0f64..=1f64).contains(&value) {
+                    return Err(ConfigError::OutOfUnitRange(name, value));
+                }
+            }
+            if low > high {
+                return Err(ConfigError::BackwardsRange(name, low, high));
+            }
+        }
+        if let Some(max_change) = self.angles.max_change {
+            if max_change <= 0f64 || max_change.is_nan() {
+                return Err(ConfigError::NotPositive("Max angle change", max_change));
+            }
+        }
+        for fixed in self.angles.fixed.iter() {
+            if fixed.start > fixed.end {
+                return Err(ConfigError::BackwardsRange(
+                    "Fixed angle frames",
+                    fixed.start as f64,
+                    fixed.end as f64,
+                ));
+            }
+        }
+        if self.islands > 1 && self.genome != GenomeKind::PerFrame {
+            return Err(ConfigError::IslandsNeedPerFrame);
+        }
+        Ok(())
+    }
+
+    /// Only the GA with a single population can save its state and be resumed.
+    pub fn check_saveable(&self) -> Result<(), ConfigError> {
+        if self.optimizer != OptimizerKind::Genetic {
+            return Err(ConfigError::CantSave(self.optimizer.name()));
+        }
+        if self.islands > 1 {
+            return Err(ConfigError::CantSave("The island model"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(toml: &str) -> Result<Result<(), ConfigError>, toml::de::Error> {
+        toml::from_str::<SolverConfig>(toml).map(|c| c.validate())
+    }
+
+    #[test]
+    fn default_config_test() {
+        assert!(SolverConfig::default().validate().is_ok());
+        assert!(SolverConfig::default().check_saveable().is_ok());
+    }
+
+    #[test]
+    fn population_config_test() {
+        assert!(matches!(
+            validate("population_size = 8"),
+            Ok(Err(ConfigError::PopulationTooSmall(8, 12)))
+        ));
+        assert!(matches!(
+            validate("mutation_rate = 1.5"),
+            Ok(Err(ConfigError::OutOfUnitRange("Mutation rate", _)))
+        ));
+    }
+
+    #[test]
+    fn optimizer_config_test() {
+        let config: Result<SolverConfig, _> = toml::from_str("optimizer = \"cma_es\"");
+        assert!(matches!(
+            config,
+            Ok(SolverConfig {
+                optimizer: OptimizerKind::CmaEs,
+                ..
+            })
+        ));
+        assert!(matches!(
+            validate("search_max_frames = 0"),
+            Ok(Err(ConfigError::Zero("Search max frames")))
+        ));
+    }
+
+    #[test]
+    fn fitness_config_test() {
+        assert!(matches!(
+            validate("[fitness]\nend_speed = -1.0"),
+            Ok(Err(ConfigError::Negative("End speed weight", _)))
+        ));
+    }
+
+    #[test]
+    fn islands_config_test() {
+        assert!(matches!(
+            validate("islands = 4\ngenome = \"segments\""),
+            Ok(Err(ConfigError::IslandsNeedPerFrame))
+        ));
+    }
+
+    #[test]
+    fn saveable_config_test() {
+        let config: Result<SolverConfig, _> = toml::from_str("optimizer = \"beam_search\"");
+        assert!(matches!(
+            config.map(|c| c.check_saveable()),
+            Ok(Err(ConfigError::CantSave("Beam search")))
+        ));
+    }
+
+    #[test]
+    fn exit_config_test() {
+        assert!(matches!(
+            validate("[exit]\nsubpixel_x = [0.8, 0.2]"),
+            Ok(Err(ConfigError::BackwardsRange(
+                "Exit horizontal subpixel",
+                _,
+                _
+            )))
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use super::geneticalg::Inputs;
+use crate::player::DASH_OFFSET;
+
+/// An angle every frame from `start` up to but not including `end` has to hold, counting from the
+/// first frame of the path.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FixedAngle {
+    pub start: usize,
+    pub end: usize,
+    pub angle: f64,
+}
+
+/// Limits on which angles the paths can use. Dashes are left alone, only where they aim is limited.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AngleConstraints {
+    /// Ranges every angle has to be inside one of, going clockwise from the first angle to the
+    /// second. Any angle is fine when there are none.
+    pub allowed: Vec<[f64; 2]>,
+    pub fixed: Vec<FixedAngle>,
+    /// The most the angle can turn from one frame to the next, in degrees.
+    pub max_change: Option<f64>,
+}
+
+/// How far clockwise `to` is from `from`, between -180 and 180.
+fn turn(from: f64, to: f64) -> f64 {
+    (to - from + 180f64).rem_euclid(360f64) - 180f64
+}
+
+fn in_range(angle: f64, [from, to]: [f64; 2]) -> bool {
+    if to - from >= 360f64 {
+        return true;
+    }
+    (angle - from).rem_euclid(360f64) <= (to - from).rem_euclid(360f64)
+}
+
+impl AngleConstraints {
+    pub fn is_empty(&self) -> bool {
+        self.allowed.is_empty() && self.fixed.is_empty() && self.max_change.is_none()
+    }
+
+    /// The closest angle to `angle` that's inside one of the allowed ranges.
+    fn nearest_allowed(&self, angle: f64) -> f64 {
+        if self.allowed.is_empty() || self.allowed.iter().any(|&range| in_range(angle, range)) {
+            return angle;
+        }
+        self.allowed
+            .iter()
+            .flatten()
+            .copied()
+            .min_by(|&a, &b| turn(angle, a).abs().total_cmp(&turn(angle, b).abs()))
+            .unwrap_or(angle)
+    }
+
+    /// The gene that actually gets used on `frame` in place of `gene`, given the one used on the
+    /// frame before. Fixed angles win over everything else, then the allowed ranges win over
+    /// `max_change` if they disagree. Genes that already follow every constraint come back as is.
+    pub fn constrain(&self, frame: usize, gene: f64, previous: Option<f64>) -> f64 {
+        if self.is_empty() {
+            return gene;
+        }
+        let dash = gene >= DASH_OFFSET;
+        let mut angle = if dash { gene - DASH_OFFSET } else { gene };
+        if let Some(fixed) = self
+            .fixed
+            .iter()
+            .find(|fixed| (fixed.start..fixed.end).contains(&frame))
+        {
+            angle = fixed.angle.rem_euclid(360f64);
+        } else {
+            if let (Some(max_change), Some(previous)) = (self.max_change, previous) {
+                let previous = previous % DASH_OFFSET;
+                let change = turn(previous, angle);
+                if change.abs() > max_change {
+                    angle = (previous + max_change.copysign(change)).rem_euclid(360f64);
+                }
+            }
+            angle = self.nearest_allowed(angle).rem_euclid(360f64);
+        }
+        // rem_euclid can round up to exactly 360, which would be a dash
+        if angle >= 360f64 {
+            angle = 0f64;
+        }
+        if dash {
+            angle + DASH_OFFSET
+        } else {
+            angle
+        }
+    }
+
+    /// Makes every gene in `inputs` follow the constraints, the same way the simulator does.
+    pub fn apply(&self, inputs: &mut Inputs) {
+        if self.is_empty() {
+            return;
+        }
+        let mut previous = None;
+        for (frame, gene) in inputs.iter_mut().enumerate() {
+            *gene = self.constrain(frame, *gene, previous);
+            previous = Some(*gene);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn angle_constraints_test() {
+        let constraints = AngleConstraints {
+            allowed: vec![[300f64, 90f64]],
+            fixed: vec![FixedAngle {
+                start: 3,
+                end: 5,
+                angle: 45f64,
+            }],
+            max_change: Some(20f64),
+        };
+        let mut inputs = vec![180f64, 350f64, 180f64, 0f64, DASH_OFFSET + 270f64, 80f64];
+        constraints.apply(&mut inputs);
+        // 90 is closer than 300
+        assert_eq!(inputs[0], 90f64);
+        // turning 100 degrees anticlockwise gets cut down to 20
+        assert_eq!(inputs[1], 70f64);
+        assert_eq!(inputs[2], 90f64);
+        assert_eq!(inputs[3], 45f64);
+        assert_eq!(inputs[4], DASH_OFFSET + 45f64);
+        assert_eq!(inputs[5], 65f64);
+        let before = inputs.clone();
+        constraints.apply(&mut inputs);
+        assert_eq!(inputs, before);
+    }
+}
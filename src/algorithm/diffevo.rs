@@ -0,0 +1,107 @@
+use genevo::random::{Prng, Rng};
+use rayon::prelude::*;
+
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, Simulator};
+use super::optimizer::{
+    gene_difference, gene_range, grow_horizon, seeded_rng, wrap_gene, Optimizer,
+};
+use super::progress::RunContext;
+
+/// DE/rand/1/bin, keeping the path it was warm started from as one of the population.
+pub(super) struct DifferentialEvolution {
+    config: SolverConfig,
+    rng: Prng,
+}
+
+impl DifferentialEvolution {
+    pub fn new(config: &SolverConfig) -> Self {
+        Self {
+            config: config.clone(),
+            rng: seeded_rng(config),
+        }
+    }
+
+    /// Picks an index that isn't any of `exclude`.
+    fn pick_other(&mut self, size: usize, exclude: &[usize]) -> usize {
+        loop {
+            let index = self.rng.gen_range(0..size);
+            if !exclude.contains(&index) {
+                return index;
+            }
+        }
+    }
+
+    fn best(fitnesses: &[f64]) -> usize {
+        (0..fitnesses.len())
+            .max_by(|&a, &b| fitnesses[a].total_cmp(&fitnesses[b]))
+            .unwrap_or_default()
+    }
+
+    fn search(&mut self, simulator: &Simulator, ctx: &RunContext, start: &Inputs) -> Inputs {
+        let range = gene_range(&self.config);
+        let size = self.config.population_size;
+        let weight = self.config.de_differential_weight;
+        let mut population = vec![start
+            .iter()
+            .map(|&g| wrap_gene(g, range))
+            .collect::<Inputs>()];
+        population.extend((1..size).map(|_| {
+            (0..start.len())
+                .map(|_| self.rng.gen_range(0f64..range))
+                .collect::<Inputs>()
+        }));
+        let mut fitnesses = population
+            .par_iter()
+            .map(|x| simulator.fitness(x))
+            .collect::<Vec<_>>();
+        for generation in 0..self.config.generation_limit {
+            if ctx.cancelled() {
+                break;
+            }
+            let trials = (0..size)
+                .map(|i| {
+                    let a = self.pick_other(size, &[i]);
+                    let b = self.pick_other(size, &[i, a]);
+                    let c = self.pick_other(size, &[i, a, b]);
+                    let forced = self.rng.gen_range(0..start.len());
+                    (0..start.len())
+                        .map(|j| {
+                            if j == forced || self.rng.gen::<f64>() < self.config.de_crossover_rate
+                            {
+                                let diff =
+                                    gene_difference(population[b][j], population[c][j], range);
+                                wrap_gene(population[a][j] + weight * diff, range)
+                            } else {
+                                population[i][j]
+                            }
+                        })
+                        .collect::<Inputs>()
+                })
+                .collect::<Vec<_>>();
+            let trial_fitnesses = trials
+                .par_iter()
+                .map(|x| simulator.fitness(x))
+                .collect::<Vec<_>>();
+            for (i, (trial, fitness)) in trials.into_iter().zip(trial_fitnesses).enumerate() {
+                if fitness >= fitnesses[i] {
+                    population[i] = trial;
+                    fitnesses[i] = fitness;
+                }
+            }
+            ctx.report(simulator, generation, &population[Self::best(&fitnesses)]);
+        }
+        population.swap_remove(Self::best(&fitnesses))
+    }
+}
+
+impl Optimizer for DifferentialEvolution {
+    fn optimize(&mut self, simulator: &mut Simulator, ctx: &RunContext) -> Inputs {
+        grow_horizon(simulator, ctx, self.config.search_max_frames, |start| {
+            self.search(simulator, ctx, start)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {}
@@ -0,0 +1,205 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use ordered_float::OrderedFloat;
+
+use crate::colliders::{Collider, Direction, Rect};
+use crate::player::MovementPrecomputer;
+use crate::point::Point;
+
+const DIAGONAL_COST: f32 = std::f32::consts::SQRT_2;
+
+/// How far every pixel of the level is from a checkpoint when the player has to go around solids
+/// and death to get there, rather than straight through them.
+#[derive(Clone, Debug)]
+pub(super) struct DistanceField {
+    origin: (i32, i32),
+    width: i32,
+    height: i32,
+    distances: Vec<f32>,
+    /// The furthest any reachable pixel is from the checkpoint.
+    furthest: f32,
+}
+
+impl DistanceField {
+    /// Dijkstra outwards from every position where the player would touch `target`, only stepping
+    /// between positions the precomputer says the player can move between without dying.
+    pub fn new(precomputed: &MovementPrecomputer, target: &Rect) -> Option<Self> {
+        if precomputed.is_empty() {
+            return None;
+        }
+        let bounds = precomputed.bounds();
+        let origin = (bounds.ul.x as i32, bounds.ul.y as i32);
+        let width = bounds.dr.x as i32 - origin.0 + 1;
+        let height = bounds.dr.y as i32 - origin.1 + 1;
+        let mut field = Self {
+            origin,
+            width,
+            height,
+            distances: vec![f32::INFINITY; (width * height) as usize],
+            furthest: 0f32,
+        };
+        let mut queue = BinaryHeap::new();
+        // the player's hitbox spans 4 left to 3 right of its position, and 11 up to 1 up
+        let (x_min, x_max) = (target.ul.x as i32 - 4, target.dr.x as i32 + 5);
+        let (y_min, y_max) = (target.ul.y as i32, target.dr.y as i32 + 12);
+        let target = Collider::Rectangular(*target);
+        for y in i32::max(y_min, origin.1)..=i32::min(y_max, origin.1 + height - 1) {
+            for x in i32::max(x_min, origin.0)..=i32::min(x_max, origin.0 + width - 1) {
+                let pos = Point::new(x as f32, y as f32);
+                if precomputed.get_death_prerounded(&pos, Direction::Up) {
+                    continue;
+                }
+                let hitbox = Collider::Rectangular(Rect::new(
+                    Point::new(pos.x - 4f32, pos.y - 11f32),
+                    Point::new(pos.x + 3f32, pos.y - 1f32),
+                ));
+                if hitbox.collide_check(&target) {
+                    let index = field.index(x, y);
+                    field.distances[index] = 0f32;
+                    queue.push(Reverse((OrderedFloat(0f32), x, y)));
+                }
+            }
+        }
+        while let Some(Reverse((OrderedFloat(distance), x, y))) = queue.pop() {
+            if distance > field.distances[field.index(x, y)] {
+                continue;
+            }
+            for (dx, dy) in itertools::iproduct!(-1..=1, -1..=1) {
+                let (nx, ny) = (x + dx, y + dy);
+                if (dx, dy) == (0, 0) || !field.contains(nx, ny) {
+                    continue;
+                }
+                // the field gets built backwards, so check the move from the neighbour to here
+                if !Self::can_step(precomputed, nx, ny, -dx, -dy) {
+                    continue;
+                }
+                let cost = if dx != 0 && dy != 0 {
+                    DIAGONAL_COST
+                } else {
+                    1f32
+                };
+                let index = field.index(nx, ny);
+                if distance + cost < field.distances[index] {
+                    field.distances[index] = distance + cost;
+                    queue.push(Reverse((OrderedFloat(distance + cost), nx, ny)));
+                }
+            }
+        }
+        field.furthest = field
+            .distances
+            .iter()
+            .copied()
+            .filter(|d| d.is_finite())
+            .fold(0f32, f32::max);
+        Some(field)
+    }
+
+    /// Whether the player can get from `(x, y)` to `(x + dx, y + dy)` without hitting anything.
+    /// Diagonals need both of the straight moves around them to be clear as well.
+    fn can_step(precomputed: &MovementPrecomputer, x: i32, y: i32, dx: i32, dy: i32) -> bool {
+        let free = |x: i32, y: i32, direction: Direction| {
+            let pos = Point::new(x as f32, y as f32);
+            precomputed.get_solid_prerounded(&pos, direction) > 0
+        };
+        let horizontal = match dx {
+            -1 => Some(Direction::Left),
+            1 => Some(Direction::Right),
+            _ => None,
+        };
+        let vertical = match dy {
+            -1 => Some(Direction::Up),
+            1 => Some(Direction::Down),
+            _ => None,
+        };
+        let to = Point::new((x + dx) as f32, (y + dy) as f32);
+        if precomputed.get_death_prerounded(&to, Direction::Up) {
+            return false;
+        }
+        match (horizontal, vertical) {
+            (Some(h), Some(v)) => {
+                free(x, y, h) && free(x, y, v) && free(x + dx, y, v) && free(x, y + dy, h)
+            }
+            (Some(h), None) => free(x, y, h),
+            (None, Some(v)) => free(x, y, v),
+            (None, None) => true,
+        }
+    }
+
+    #[inline]
+    fn contains(&self, x: i32, y: i32) -> bool {
+        (self.origin.0..self.origin.0 + self.width).contains(&x)
+            && (self.origin.1..self.origin.1 + self.height).contains(&y)
+    }
+
+    #[inline]
+    fn index(&self, x: i32, y: i32) -> usize {
+        ((x - self.origin.0) + (y - self.origin.1) * self.width) as usize
+    }
+
+    fn get(&self, x: i32, y: i32) -> Option<f32> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        Some(self.distances[self.index(x, y)]).filter(|d| d.is_finite())
+    }
+
+    /// What a position with no way to the checkpoint counts as, which is further than anywhere
+    /// that does have one.
+    pub fn unreachable(&self) -> f64 {
+        (self.furthest + DIAGONAL_COST) as f64
+    }
+
+    /// Bilinearly interpolates between the 4 pixels around `pos` so subpixels still count,
+    /// falling back to the nearest pixel next to walls. `None` if there's no way to the checkpoint.
+    pub fn distance(&self, pos: Point) -> Option<f64> {
+        let (x, y) = (pos.x.floor() as i32, pos.y.floor() as i32);
+        let (fx, fy) = (pos.x - pos.x.floor(), pos.y - pos.y.floor());
+        match (
+            self.get(x, y),
+            self.get(x + 1, y),
+            self.get(x, y + 1),
+            self.get(x + 1, y + 1),
+        ) {
+            (Some(a), Some(b), Some(c), Some(d)) => {
+                let top = a + (b - a) * fx;
+                let bottom = c + (d - c) * fx;
+                Some((top + (bottom - top) * fy) as f64)
+            }
+            _ => {
+                let rounded = pos.round();
+                self.get(rounded.x as i32, rounded.y as i32)
+                    .map(|d| d as f64)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstar::RTree;
+
+    #[test]
+    fn distance_field_test() {
+        // a wall straight down the middle with a gap at the bottom
+        let solids = RTree::bulk_load(vec![Collider::Rectangular(Rect::new_xywh(
+            -4f32, -64f32, 8f32, 96f32,
+        ))]);
+        let bounds = Rect::new_xywh(-64f32, -64f32, 128f32, 128f32);
+        let precomputed = MovementPrecomputer::new(&solids, &RTree::bulk_load(vec![]), bounds);
+        let target = Rect::new_xywh(30f32, -20f32, 4f32, 4f32);
+        let Some(field) = DistanceField::new(&precomputed, &target) else {
+            panic!("precomputer shouldn't be empty");
+        };
+        let start = Point::new(-30f32, -10f32);
+        let Some(distance) = field.distance(start) else {
+            panic!("should be able to go around the wall");
+        };
+        // has to go down under the wall and back up again
+        assert!(distance > 100f64);
+        assert!(field.distance(Point::new(0f32, 0f32)).is_none());
+        assert!(field.unreachable() > distance);
+        assert_eq!(field.distance(Point::new(32f32, -10f32)), Some(0f64));
+    }
+}
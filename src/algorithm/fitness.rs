@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+
+use crate::player::Player;
+
+/// How much each term counts towards the fitness. Everything but checkpoint progress and end speed
+/// is a penalty, so bigger weights always mean the term matters more.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FitnessWeights {
+    /// Per checkpoint hit.
+    pub checkpoint: f64,
+    /// Per frame, once every checkpoint has been hit.
+    pub frames: f64,
+    /// Per frame, while there's still a checkpoint left.
+    pub unfinished_frames: f64,
+    /// Per pixel of the closest the player got to the next checkpoint.
+    pub closest_approach: f64,
+    /// Per frame it took to get that close.
+    pub closest_frame: f64,
+    /// Per pixel away from the next checkpoint at the end, or past the final one when finished.
+    pub final_distance: f64,
+    /// Per pixel per second of speed at the end.
+    pub end_speed: f64,
+    /// Per unit the exit constraints are off by, see [`ExitConstraints::violation`].
+    pub exit: f64,
+}
+
+impl Default for FitnessWeights {
+    fn default() -> Self {
+        Self {
+            checkpoint: 10000f64,
+            frames: 8f64,
+            unfinished_frames: 1f64,
+            closest_approach: 0f64,
+            closest_frame: 0f64,
+            final_distance: 1f64,
+            end_speed: 0f64,
+            exit: 10f64,
+        }
+    }
+}
+
+/// What the player has to be doing as the final checkpoint gets touched, so the path sets up
+/// whatever comes after it. Anything left unset doesn't matter.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExitConstraints {
+    /// In pixels per second, with right and down being positive.
+    pub min_speed_x: Option<f64>,
+    pub max_speed_x: Option<f64>,
+    pub min_speed_y: Option<f64>,
+    pub max_speed_y: Option<f64>,
+    /// Range the fractional part of the player's position has to be in.
+    pub subpixel_x: Option<[f64; 2]>,
+    pub subpixel_y: Option<[f64; 2]>,
+    /// Which way the player has to be moving, in degrees the same way as the inputs.
+    pub direction: Option<f64>,
+    /// How many degrees either side of `direction` still counts.
+    pub direction_tolerance: f64,
+    /// Whether a path that breaks any of these doesn't count as finishing at all, rather than
+    /// just losing fitness for it.
+    pub hard: bool,
+}
+
+impl Default for ExitConstraints {
+    fn default() -> Self {
+        Self {
+            min_speed_x: None,
+            max_speed_x: None,
+            min_speed_y: None,
+            max_speed_y: None,
+            subpixel_x: None,
+            subpixel_y: None,
+            direction: None,
+            direction_tolerance: 5f64,
+            hard: false,
+        }
+    }
+}
+
+impl ExitConstraints {
+    /// How far off the player is from meeting every constraint, or 0 if they all hold. Speeds count
+    /// per pixel per second, subpixels per hundredth of a pixel and directions per degree.
+    pub fn violation(&self, player: &Player) -> f64 {
+        let (speed, pos) = (player.speed, player.pos());
+        let below = |value: f32, min: Option<f64>| min.map_or(0f64, |min| min - value as f64);
+        let above = |value: f32, max: Option<f64>| max.map_or(0f64, |max| value as f64 - max);
+        let outside = |value: f32, range: Option<[f64; 2]>| {
+            let fraction = (value - value.floor()) as f64;
+            range.map_or(0f64, |[low, high]| {
+                100f64 * f64::max(low - fraction, fraction - high)
+            })
+        };
+        let mut violation = [
+            below(speed.x, self.min_speed_x),
+            above(speed.x, self.max_speed_x),
+            below(speed.y, self.min_speed_y),
+            above(speed.y, self.max_speed_y),
+            outside(pos.x, self.subpixel_x),
+            outside(pos.y, self.subpixel_y),
+        ]
+        .into_iter()
+        .map(|amount| amount.max(0f64))
+        .sum::<f64>();
+        if let Some(direction) = self.direction {
+            // 0 is up and 90 is right, like the inputs
+            let moving = (speed.x as f64).atan2(-speed.y as f64).to_degrees();
+            let off = ((moving - direction + 180f64).rem_euclid(360f64) - 180f64).abs();
+            violation += (off - self.direction_tolerance).max(0f64);
+        }
+        violation
+    }
+}
+
+/// What each term added to a path's fitness, after weighting.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FitnessBreakdown {
+    pub checkpoint: f64,
+    pub frames: f64,
+    pub closest_approach: f64,
+    pub closest_frame: f64,
+    pub final_distance: f64,
+    pub end_speed: f64,
+    pub exit: f64,
+}
+
+impl FitnessBreakdown {
+    pub fn total(&self) -> f64 {
+        self.terms().iter().map(|(_, value)| value).sum()
+    }
+
+    pub fn terms(&self) -> [(&'static str, f64); 7] {
+        [
+            ("Checkpoints", self.checkpoint),
+            ("Frames", self.frames),
+            ("Closest approach", self.closest_approach),
+            ("Frame of closest approach", self.closest_frame),
+            ("Final distance", self.final_distance),
+            ("End speed", self.end_speed),
+            ("Exit state", self.exit),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+
+    #[test]
+    fn exit_violation_test() {
+        let player = Player::new(Point::new(60f32, -20f32), Point::new(10.25f32, 4f32));
+        let mut constraints = ExitConstraints::default();
+        assert_eq!(constraints.violation(&player), 0f64);
+        constraints.min_speed_x = Some(50f64);
+        constraints.subpixel_x = Some([0.2, 0.3]);
+        assert_eq!(constraints.violation(&player), 0f64);
+        constraints.min_speed_x = Some(70f64);
+        assert_eq!(constraints.violation(&player), 10f64);
+        constraints.min_speed_x = None;
+        constraints.subpixel_x = Some([0.5, 0.75]);
+        assert_eq!(constraints.violation(&player), 25f64);
+        // moving right and up, so somewhere between 0 and 90
+        constraints.subpixel_x = None;
+        constraints.direction = Some(70f64);
+        assert_eq!(constraints.violation(&player), 0f64);
+        constraints.direction = Some(0f64);
+        assert!(constraints.violation(&player) > 60f64);
+    }
+}
@@ -1,17 +1,28 @@
+use std::fmt::Debug;
 use std::sync::{Arc, Mutex};
 
+use super::config::SolverConfig;
+use super::constraints::AngleConstraints;
+use super::distance::DistanceField;
+use super::fitness::{ExitConstraints, FitnessBreakdown, FitnessWeights};
+use super::max_gene;
+use super::simcache::SimCache;
+use super::waterspeed::SwimMutator;
 use crate::colliders::Rect;
 use crate::level::Level;
-use crate::player::{FrameResult, Player};
+use crate::player::{FrameResult, Player, SWIM_MAX_Y};
+use crate::point::Point;
 
 use genevo::genetic::{Children, Parents};
 use genevo::operator::prelude::RandomGenomeMutation;
+use genevo::operator::{CrossoverOp, GeneticOperator, MutationOp};
 use genevo::prelude::*;
 use genevo::recombination::discrete::MultiPointCrossover;
 use ordered_float::OrderedFloat;
+use rayon::prelude::*;
 
 #[derive(PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
-pub struct OrdFloat64(OrderedFloat<f64>);
+pub struct OrdFloat64(pub(super) OrderedFloat<f64>);
 
 impl Fitness for OrdFloat64 {
     fn zero() -> Self {
@@ -74,20 +85,188 @@ impl RandomGenomeMutation for InputsPop {
     }
 }
 
-pub(super) struct InputsBuilder;
+pub(super) struct InputsBuilder {
+    pub max_gene: f64,
+    pub length: usize,
+    pub angles: AngleConstraints,
+}
 
 impl GenomeBuilder<InputsPop> for InputsBuilder {
-    fn build_genome<R>(&self, size: usize, rng: &mut R) -> InputsPop
+    // genevo passes each genome's index in the population here, not a length
+    fn build_genome<R>(&self, _index: usize, rng: &mut R) -> InputsPop
     where
         R: Rng + Sized,
     {
-        InputsPop(
-            (0..size).map(|_| rng.gen_range(0f64..360f64)).collect(),
-            Arc::new(Mutex::new(None)),
-        )
+        let mut genome = (0..self.length)
+            .map(|_| rng.gen_range(0f64..=self.max_gene))
+            .collect();
+        self.angles.apply(&mut genome);
+        InputsPop(genome, Arc::new(Mutex::new(None)))
     }
 }
 
+/// Cuts every parent at the same random frame and puts its start onto the next parent's end, so
+/// genomes of different lengths can breed without their genes ending up on different frames.
+/// Each child is as long as the parent its end came from.
+#[derive(Clone, Debug)]
+pub(super) struct FrameCrossBreeder;
+
+impl GeneticOperator for FrameCrossBreeder {
+    fn name() -> String {
+        "Frame-Cross-Breeder".to_string()
+    }
+}
+
+impl CrossoverOp<InputsPop> for FrameCrossBreeder {
+    fn crossover<R>(&self, parents: Parents<InputsPop>, rng: &mut R) -> Children<InputsPop>
+    where
+        R: Rng + Sized,
+    {
+        let shortest = parents.iter().map(|p| p.0.len()).min().unwrap_or(0);
+        let cut = rng.gen_range(0..=shortest);
+        (0..parents.len())
+            .map(|i| {
+                let tail = &parents[(i + 1) % parents.len()].0[cut..];
+                let child = parents[i].0[..cut].iter().chain(tail).copied().collect();
+                InputsPop(child, Arc::new(Mutex::new(None)))
+            })
+            .collect()
+    }
+}
+
+/// Another operator, but with everything it makes pulled back in line with the angle constraints.
+#[derive(Clone, Debug)]
+pub(super) struct Constrained<O> {
+    pub inner: O,
+    pub angles: AngleConstraints,
+}
+
+impl<O: GeneticOperator> GeneticOperator for Constrained<O> {
+    fn name() -> String {
+        format!("Constrained-{}", O::name())
+    }
+}
+
+impl<G: Genome, O: CrossoverOp<G>> CrossoverOp<G> for Constrained<O> {
+    fn crossover<R>(&self, parents: Parents<G>, rng: &mut R) -> Children<G>
+    where
+        R: Rng + Sized,
+    {
+        let mut children = self.inner.crossover(parents, rng);
+        for child in children.iter_mut() {
+            child.constrain(&self.angles);
+        }
+        children
+    }
+}
+
+impl<G: Genome, O: MutationOp<G>> MutationOp<G> for Constrained<O> {
+    fn mutate<R>(&self, genome: G, rng: &mut R) -> G
+    where
+        R: Rng + Sized,
+    {
+        let mut genome = self.inner.mutate(genome, rng);
+        genome.constrain(&self.angles);
+        genome
+    }
+}
+
+/// A way of laying out a path for the GA to evolve. Everything outside the GA sees it as one gene
+/// per frame.
+pub(super) trait Genome: Genotype {
+    type Builder: GenomeBuilder<Self>;
+    type Crossover: CrossoverOp<Self> + Debug + Sync;
+    type Mutation<'a>: MutationOp<Self> + Debug + Sync;
+
+    /// Builds random genomes `frames` frames long.
+    fn builder(config: &SolverConfig, frames: usize) -> Self::Builder;
+    fn crossover(config: &SolverConfig) -> Self::Crossover;
+    fn mutation<'a>(
+        simulator: &Simulator<'a>, config: &SolverConfig, mutation_rate: f64,
+    ) -> Self::Mutation<'a>;
+    /// Conversions to and from one gene per frame, which keep the fitness if it's known.
+    fn from_inputs(individual: InputsPop) -> Self;
+    fn to_inputs(&self) -> InputsPop;
+    fn frames(&self) -> usize;
+    /// Holds `gene` on the end until it's `frames` frames long.
+    fn extend(&mut self, frames: usize, gene: f64);
+    fn constrain(&mut self, angles: &AngleConstraints);
+}
+
+impl Genome for InputsPop {
+    type Builder = InputsBuilder;
+    type Crossover = Constrained<FrameCrossBreeder>;
+    type Mutation<'a> = Constrained<SwimMutator<'a>>;
+
+    fn builder(config: &SolverConfig, frames: usize) -> Self::Builder {
+        InputsBuilder {
+            max_gene: max_gene(config),
+            length: frames,
+            angles: config.angles.clone(),
+        }
+    }
+
+    fn crossover(config: &SolverConfig) -> Self::Crossover {
+        Constrained {
+            inner: FrameCrossBreeder,
+            angles: config.angles.clone(),
+        }
+    }
+
+    fn mutation<'a>(
+        simulator: &Simulator<'a>, config: &SolverConfig, mutation_rate: f64,
+    ) -> Self::Mutation<'a> {
+        Constrained {
+            inner: SwimMutator {
+                simulator: simulator.clone(),
+                mutation_rate,
+                max_gene: max_gene(config),
+                bias: config.swim_bias,
+            },
+            angles: config.angles.clone(),
+        }
+    }
+
+    fn from_inputs(individual: InputsPop) -> Self {
+        individual
+    }
+
+    fn to_inputs(&self) -> InputsPop {
+        self.clone()
+    }
+
+    fn frames(&self) -> usize {
+        self.0.len()
+    }
+
+    fn extend(&mut self, frames: usize, gene: f64) {
+        self.0.resize(frames, gene);
+        // copies made during reinsertion share a cache, but they're different genomes now
+        self.1 = Arc::new(Mutex::new(None));
+    }
+
+    fn constrain(&mut self, angles: &AngleConstraints) {
+        angles.apply(&mut self.0);
+    }
+}
+
+/// Everything about a run that changes from frame to frame.
+#[derive(Clone, Debug)]
+pub(super) struct SimState {
+    pub player: Player,
+    pub prev_player: Player,
+    pub checkpoint_index: usize,
+    pub frame_count: usize,
+    /// The closest the player has been to the next checkpoint since hitting the last one.
+    pub closest_distance: f64,
+    pub closest_frame: usize,
+    /// The gene used on the last frame, after the angle constraints.
+    pub previous_gene: Option<f64>,
+    /// The player on the frame the final checkpoint got hit, which is what the exit gets judged on
+    /// even if the path keeps going after it.
+    pub exit_player: Option<Player>,
+}
+
 #[derive(Clone, Debug)]
 pub(super) struct Simulator<'a> {
     player: Player,
@@ -95,51 +274,344 @@ pub(super) struct Simulator<'a> {
     checkpoints: Vec<Rect>,
     base_checkpoint: usize,
     base_frame: usize,
+    weights: FitnessWeights,
+    exit: ExitConstraints,
+    angles: AngleConstraints,
+    /// One per checkpoint, unless the level has no precomputed movement.
+    fields: Vec<Option<DistanceField>>,
+    /// Shared between clones, since they all simulate the same way.
+    cache: Option<Arc<SimCache>>,
 }
 
 impl<'a> Simulator<'a> {
     pub fn new(player: Player, level: &'a Level, checkpoints: Vec<Rect>) -> Self {
+        let fields = checkpoints
+            .par_iter()
+            .map(|checkpoint| DistanceField::new(&level.precomputed, checkpoint))
+            .collect();
         Self {
             player,
             level,
             checkpoints,
             base_checkpoint: 0,
             base_frame: 0,
+            weights: FitnessWeights::default(),
+            exit: ExitConstraints::default(),
+            angles: AngleConstraints::default(),
+            fields,
+            cache: None,
         }
     }
 
-    fn sim_player(&self, inp: &Inputs) -> (Player, Player, usize, usize) {
-        //let now = SystemTime::now();
-        let mut player = self.player.clone();
-        let mut prev_player = player.clone();
-        let mut checkpoint_index = self.base_checkpoint;
-        let mut frame_count = self.base_frame;
-        for &i in inp {
-            frame_count += 1;
-            prev_player = player.clone();
-            player.speed_calc(i, self.level); // TODO: restrict
-            player.move_self(self.level);
-            if checkpoint_index == self.checkpoints.len() {
+    /// Starts every run from the given checkpoint and frame rather than the beginning.
+    pub fn with_base(mut self, base_checkpoint: usize, base_frame: usize) -> Self {
+        self.base_checkpoint = base_checkpoint;
+        self.base_frame = base_frame;
+        self.clear_cache();
+        self
+    }
+
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
+    pub fn base(&self) -> (usize, usize) {
+        (self.base_checkpoint, self.base_frame)
+    }
+
+    pub fn with_weights(mut self, weights: FitnessWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    pub fn with_exit(mut self, exit: ExitConstraints) -> Self {
+        self.exit = exit;
+        self
+    }
+
+    pub fn with_angles(mut self, angles: AngleConstraints) -> Self {
+        self.angles = angles;
+        self.clear_cache();
+        self
+    }
+
+    /// Remembers the state every `interval` frames into each genome so later genomes with the
+    /// same start can skip ahead, keeping up to `capacity` of them. An interval of 0 turns it off.
+    pub fn with_cache(mut self, interval: usize, capacity: usize) -> Self {
+        self.cache = (interval > 0).then(|| Arc::new(SimCache::new(interval, capacity)));
+        self
+    }
+
+    #[cfg(test)]
+    pub fn cache(&self) -> Option<&SimCache> {
+        self.cache.as_deref()
+    }
+
+    /// Throws away every snapshot, for when they no longer match how the simulator would get there.
+    fn clear_cache(&mut self) {
+        if let Some(cache) = &self.cache {
+            self.cache = Some(Arc::new(SimCache::new(cache.interval(), cache.capacity())));
+        }
+    }
+
+    /// Makes `inputs` follow the angle constraints, so they're what actually got simulated.
+    pub fn constrain(&self, inputs: &mut Inputs) {
+        self.angles.apply(inputs);
+    }
+
+    pub fn initial_state(&self) -> SimState {
+        SimState {
+            player: self.player.clone(),
+            prev_player: self.player.clone(),
+            checkpoint_index: self.base_checkpoint,
+            frame_count: self.base_frame,
+            closest_distance: self.target_distance(&self.player, self.base_checkpoint),
+            closest_frame: self.base_frame,
+            previous_gene: None,
+            exit_player: None,
+        }
+    }
+
+    /// How far the player has to go to reach the checkpoint at `index`, or 0 when there isn't one.
+    /// Goes around solids, with anywhere there's no way from counting as further than anywhere
+    /// there is. Only levels without precomputed movement use a straight line.
+    pub fn target_distance(&self, player: &Player, index: usize) -> f64 {
+        let Some(checkpoint) = self.checkpoints.get(index) else {
+            return 0f64;
+        };
+        if let Some(field) = &self.fields[index] {
+            return field
+                .distance(player.pos())
+                .unwrap_or_else(|| field.unreachable());
+        }
+        let player_center = match player.hitbox.rect() {
+            Some(rect) => rect,
+            None => unreachable!(),
+        }
+        .center();
+        checkpoint.center().distance(player_center) as f64
+    }
+
+    /// Which way the player should be heading to get to the next checkpoint soonest, following the
+    /// distance field down when there is one. `None` once there are no checkpoints left.
+    pub fn target_direction(&self, state: &SimState) -> Option<Point> {
+        let checkpoint = self.checkpoints.get(state.checkpoint_index)?;
+        let pos = state.player.pos();
+        if let Some(field) = &self.fields[state.checkpoint_index] {
+            let slope = |offset: Point| {
+                Some((field.distance(pos + offset)? - field.distance(pos - offset)?) as f32)
+            };
+            if let (Some(x), Some(y)) =
+                (slope(Point::new(1f32, 0f32)), slope(Point::new(0f32, 1f32)))
+            {
+                let downhill = Point::new(-x, -y);
+                if downhill.magnitude() > 0f32 {
+                    return Some(downhill.normalize());
+                }
+            }
+        }
+        let player_center = match state.player.hitbox.rect() {
+            Some(rect) => rect,
+            None => unreachable!(),
+        }
+        .center();
+        let towards = checkpoint.center() - player_center;
+        (towards.magnitude() > 0f32).then(|| towards.normalize())
+    }
+
+    /// Simulates a single frame, returning whether there's any point simulating more after it.
+    pub fn step(&self, state: &mut SimState, gene: f64) -> bool {
+        // constrained before anything else so frozen frames still count towards the max change
+        let frame = state.frame_count - self.base_frame;
+        let gene = self.angles.constrain(frame, gene, state.previous_gene);
+        state.previous_gene = Some(gene);
+        state.frame_count += 1;
+        state.prev_player = state.player.clone();
+        if !state.player.timing.tick() {
+            return true;
+        }
+        state.player.speed_calc(gene, self.level);
+        state.player.move_self(self.level);
+        state.player.update_entities(self.level);
+        if state.checkpoint_index == self.checkpoints.len() {
+            return false;
+        }
+        match state
+            .player
+            .collide(self.level, &self.checkpoints[state.checkpoint_index])
+        {
+            FrameResult::Death => {
+                state.player.alive = false;
+                return false;
+            }
+            FrameResult::CheckpointHit => {
+                state.checkpoint_index += 1;
+                state.closest_distance = f64::INFINITY;
+                if self.reached_final(state) {
+                    state.exit_player = Some(state.player.clone());
+                }
+            }
+            FrameResult::Nothing => (),
+        }
+        let distance = self.target_distance(&state.player, state.checkpoint_index);
+        if distance < state.closest_distance {
+            state.closest_distance = distance;
+            state.closest_frame = state.frame_count;
+        }
+        true
+    }
+
+    pub fn sim_player(&self, inp: &Inputs) -> SimState {
+        let Some(cache) = &self.cache else {
+            let mut state = self.initial_state();
+            for &i in inp {
+                if !self.step(&mut state, i) {
+                    break;
+                }
+            }
+            return state;
+        };
+        let keys = cache.prefix_keys(inp);
+        let (start, mut state) = cache
+            .resume(inp, &keys)
+            .unwrap_or_else(|| (0, self.initial_state()));
+        let mut snapshots = Vec::new();
+        for (i, &gene) in inp.iter().enumerate().skip(start) {
+            if !self.step(&mut state, gene) {
                 break;
             }
-            match player.collide(self.level, &self.checkpoints[checkpoint_index]) {
-                FrameResult::Death => break,
-                FrameResult::CheckpointHit => checkpoint_index += 1,
-                FrameResult::Nothing => (),
+            // only states that keep going get saved, so skipping to one never skips a stop
+            if (i + 1) % cache.interval() == 0 {
+                snapshots.push((keys[i / cache.interval()], state.clone()));
             }
         }
-        //println!("{}", now.elapsed().unwrap().as_secs_f64());
-        (player, prev_player, checkpoint_index, frame_count)
+        cache.store(inp, snapshots);
+        state
     }
 
     // TODO: this function name is bad
     pub fn move_own_player(&mut self, inp: &Inputs) {
-        (self.player, _, self.base_checkpoint, self.base_frame) = self.sim_player(inp);
+        let state = self.sim_player(inp);
+        (self.player, self.base_checkpoint, self.base_frame) =
+            (state.player, state.checkpoint_index, state.frame_count);
+        self.clear_cache();
+    }
+
+    /// Whether every checkpoint got hit, whatever the exit constraints say about it.
+    #[inline]
+    pub fn reached_final(&self, state: &SimState) -> bool {
+        state.checkpoint_index == self.checkpoints.len()
+    }
+
+    /// Whether every checkpoint got hit, leaving in a way that meets the exit constraints if
+    /// they're hard ones.
+    pub fn hit_final(&self, state: &SimState) -> bool {
+        self.reached_final(state) && !(self.exit.hard && self.exit_violation(state) > 0f64)
+    }
+
+    /// How far off the exit constraints the player was on the frame it hit the final checkpoint.
+    fn exit_violation(&self, state: &SimState) -> f64 {
+        self.exit
+            .violation(state.exit_player.as_ref().unwrap_or(&state.player))
     }
 
     pub fn check_if_hit_final(&self, inp: &Inputs) -> bool {
-        let result = self.sim_player(inp);
-        result.2 == self.checkpoints.len()
+        self.hit_final(&self.sim_player(inp))
+    }
+
+    /// How many of the inputs it takes to hit the final checkpoint, if they ever do.
+    pub fn frames_to_finish(&self, inp: &Inputs) -> Option<usize> {
+        self.finish(inp).map(|(frames, _)| frames)
+    }
+
+    /// How many of the inputs it takes to hit the final checkpoint and the state right as it gets
+    /// hit, if it ever does.
+    pub fn finish(&self, inp: &Inputs) -> Option<(usize, SimState)> {
+        let mut state = self.initial_state();
+        for (i, &gene) in inp.iter().enumerate() {
+            self.step(&mut state, gene);
+            if self.reached_final(&state) {
+                return self.hit_final(&state).then_some((i + 1, state));
+            }
+            if !state.player.alive {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// A rough count of the frames it'd take to get from `state` through every checkpoint left,
+    /// swimming at top speed straight between them. Dashes and boosts can beat it.
+    pub fn frames_left(&self, state: &SimState) -> usize {
+        let remaining = &self.checkpoints[state.checkpoint_index.min(self.checkpoints.len())..];
+        let distance = self.target_distance(&state.player, state.checkpoint_index)
+            + remaining
+                .windows(2)
+                .map(|pair| pair[0].center().distance(pair[1].center()) as f64)
+                .sum::<f64>();
+        let per_frame = (SWIM_MAX_Y * state.player.timing.delta_time()) as f64;
+        (distance / per_frame).ceil() as usize
+    }
+
+    pub fn checkpoints(&self) -> &[Rect] {
+        &self.checkpoints
+    }
+
+    pub fn fitness(&self, inp: &Inputs) -> f64 {
+        self.score(&self.sim_player(inp))
+    }
+
+    pub fn fitness_breakdown(&self, inp: &Inputs) -> FitnessBreakdown {
+        self.breakdown(&self.sim_player(inp))
+    }
+
+    #[inline]
+    pub fn score(&self, state: &SimState) -> f64 {
+        self.breakdown(state).total()
+    }
+
+    pub fn breakdown(&self, state: &SimState) -> FitnessBreakdown {
+        let weights = &self.weights;
+        let SimState {
+            player,
+            prev_player,
+            checkpoint_index,
+            frame_count,
+            closest_distance,
+            closest_frame,
+            ..
+        } = state;
+        let (checkpoint_index, frame_count) = (*checkpoint_index, *frame_count as f64);
+        let mut breakdown = FitnessBreakdown {
+            checkpoint: checkpoint_index as f64 * weights.checkpoint,
+            end_speed: player.speed.magnitude() as f64 * weights.end_speed,
+            ..Default::default()
+        };
+        if self.reached_final(state) {
+            let violation = self.exit_violation(state);
+            breakdown.exit = -violation * weights.exit;
+            if self.exit.hard && violation > 0f64 {
+                // worth less than any path that properly finishes
+                breakdown.exit -= weights.checkpoint;
+            }
+        }
+        if self.hit_final(state) {
+            let checkpoint = self.checkpoints[checkpoint_index - 1];
+            let (mut accurate_distance, touched) =
+                checkpoint.accurate_distance(player.pos(), prev_player.pos());
+            if !touched {
+                accurate_distance = 3.16666f64;
+            }
+            breakdown.frames = -frame_count * weights.frames;
+            breakdown.final_distance = -accurate_distance * weights.final_distance;
+        } else {
+            breakdown.frames = -frame_count * weights.unfinished_frames;
+            breakdown.closest_approach = -closest_distance * weights.closest_approach;
+            breakdown.closest_frame = -(*closest_frame as f64) * weights.closest_frame;
+            breakdown.final_distance =
+                -self.target_distance(player, checkpoint_index) * weights.final_distance;
+        }
+        breakdown
     }
 }
 
@@ -147,34 +619,7 @@ impl FitnessFunction<InputsPop, OrdFloat64> for Simulator<'_> {
     fn fitness_of(&self, inp: &InputsPop) -> OrdFloat64 {
         let mut fitness = inp.1.lock().unwrap();
         if fitness.is_none() {
-            let (player, prev_player, checkpoint_index, frame_count) = self.sim_player(&inp.0);
-            if checkpoint_index == self.checkpoints.len() {
-                let checkpoint = self.checkpoints[checkpoint_index - 1];
-                let (mut accurate_distance, touched) =
-                    checkpoint.accurate_distance(player.pos(), prev_player.pos());
-                if !touched {
-                    accurate_distance = 3.16666f64;
-                }
-                *fitness = Some(OrdFloat64(OrderedFloat(
-                    checkpoint_index as f64 * 10000f64
-                        - frame_count as f64 * 8f64
-                        - accurate_distance,
-                )));
-            } else {
-                // NOTE: this doesnt have closestDist or atFrame, i might need to add those later
-                let checkpoint = self.checkpoints[checkpoint_index];
-                let checkpoint_center = checkpoint.center();
-                let player_center = match player.hitbox.rect() {
-                    Some(rect) => rect,
-                    None => unreachable!(),
-                }
-                .center();
-                *fitness = Some(OrdFloat64(OrderedFloat(
-                    checkpoint_index as f64 * 10000f64
-                        - checkpoint_center.distance(player_center) as f64
-                        - frame_count as f64,
-                )));
-            }
+            *fitness = Some(OrdFloat64(OrderedFloat(self.fitness(&inp.0))));
         }
         fitness.unwrap()
     }
@@ -187,7 +632,7 @@ impl FitnessFunction<InputsPop, OrdFloat64> for Simulator<'_> {
 
     fn highest_possible_fitness(&self) -> OrdFloat64 {
         OrdFloat64(OrderedFloat(
-            (self.checkpoints.len() * 10000 + 10000) as f64,
+            (self.checkpoints.len() + 1) as f64 * self.weights.checkpoint,
         ))
     }
 
@@ -197,4 +642,27 @@ impl FitnessFunction<InputsPop, OrdFloat64> for Simulator<'_> {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use genevo::random::{Prng, SeedableRng};
+
+    #[test]
+    fn frame_cross_breeder_test() {
+        let parents = vec![
+            InputsPop(vec![1f64, 2f64, 3f64], Arc::new(Mutex::new(None))),
+            InputsPop(vec![10f64; 6], Arc::new(Mutex::new(None))),
+        ];
+        let mut rng = Prng::seed_from_u64(4);
+        for _ in 0..20 {
+            let children = FrameCrossBreeder.crossover(parents.clone(), &mut rng);
+            assert_eq!(children[0].0.len(), 6);
+            assert_eq!(children[1].0.len(), 3);
+            // every gene stays on the frame it came from
+            for child in children {
+                for (frame, &gene) in child.0.iter().enumerate() {
+                    assert!(gene == 10f64 || gene == parents[0].0[frame]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, SimState, Simulator};
+use super::optimizer::Optimizer;
+use super::progress::RunContext;
+use super::waterspeed::angle_toward;
+use crate::point::Point;
+
+/// How far either way from heading straight down the distance field the other headings it tries
+/// are, in degrees. Going straight down can run into a wall the field goes around.
+const HEADINGS: [f32; 7] = [0f32, -20f32, 20f32, -45f32, 45f32, -90f32, 90f32];
+
+/// Picks every frame's angle one after another, going with whichever of a few headings gets the
+/// player closest to the next checkpoint after that frame. Each heading is turned into an angle
+/// with the swim model. There's no randomness and it never dashes, so it's mostly useful as a quick
+/// baseline or a starting point for the GA.
+pub(super) struct Greedy {
+    config: SolverConfig,
+}
+
+impl Greedy {
+    pub fn new(config: &SolverConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+}
+
+fn rotate(direction: Point, degrees: f32) -> Point {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Point::new(
+        direction.x * cos - direction.y * sin,
+        direction.x * sin + direction.y * cos,
+    )
+}
+
+/// The angle and the state after it for whichever heading does best on the next frame, going by
+/// checkpoints hit and then distance to the next one. Headings that die don't count.
+fn best_step(simulator: &Simulator, state: &SimState) -> Option<(f64, SimState)> {
+    let direction = simulator.target_direction(state)?;
+    let delta_time = state.player.timing.delta_time();
+    HEADINGS
+        .iter()
+        .filter_map(|&offset| {
+            let angle = angle_toward(state.player.speed, rotate(direction, offset), delta_time);
+            let mut next = state.clone();
+            simulator.step(&mut next, angle);
+            next.player.alive.then_some((angle, next))
+        })
+        .min_by(|(_, a), (_, b)| {
+            b.checkpoint_index.cmp(&a.checkpoint_index).then_with(|| {
+                simulator
+                    .target_distance(&a.player, a.checkpoint_index)
+                    .total_cmp(&simulator.target_distance(&b.player, b.checkpoint_index))
+            })
+        })
+}
+
+/// The greedy path, stopping once it hits the final checkpoint, has nowhere left to go or is
+/// `greedy_max_frames` long.
+pub(super) fn greedy_path(
+    simulator: &Simulator, config: &SolverConfig, ctx: &RunContext,
+) -> Inputs {
+    let mut inputs = Inputs::new();
+    let mut state = simulator.initial_state();
+    for frame in 0..config.greedy_max_frames {
+        if ctx.cancelled() || simulator.hit_final(&state) {
+            break;
+        }
+        let Some((angle, next)) = best_step(simulator, &state) else {
+            break;
+        };
+        inputs.push(angle);
+        state = next;
+        ctx.report(simulator, frame as u64, &inputs);
+    }
+    inputs
+}
+
+impl Optimizer for Greedy {
+    fn optimize(&mut self, simulator: &mut Simulator, ctx: &RunContext) -> Inputs {
+        greedy_path(simulator, &self.config, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colliders::{Collider, Rect};
+    use crate::level::Level;
+    use crate::player::{MovementPrecomputer, Player};
+    use rstar::RTree;
+
+    #[test]
+    fn greedy_u_route_test() {
+        // a wall straight down the middle with a gap at the bottom, so it has to go down and back up
+        let bounds = Rect::new_xywh(-64f32, -64f32, 128f32, 128f32);
+        let mut level = Level::open_water(bounds);
+        level.solids = RTree::bulk_load(vec![Collider::Rectangular(Rect::new_xywh(
+            -4f32, -64f32, 8f32, 96f32,
+        ))]);
+        level.precomputed =
+            MovementPrecomputer::new(&level.solids, &RTree::bulk_load(vec![]), bounds);
+        let player = Player::new(Point::new(-30f32, -10f32), Point::new(0f32, 0f32));
+        let checkpoints = vec![Rect::new_xywh(30f32, -20f32, 4f32, 4f32)];
+        let simulator = Simulator::new(player, &level, checkpoints);
+        let config = SolverConfig {
+            greedy_max_frames: 300,
+            ..Default::default()
+        };
+        let path = greedy_path(&simulator, &config, &RunContext::default());
+        assert!(simulator.check_if_hit_final(&path));
+        assert_eq!(
+            path,
+            greedy_path(&simulator, &config, &RunContext::default())
+        );
+    }
+}
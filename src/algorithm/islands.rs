@@ -0,0 +1,147 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use genevo::prelude::*;
+use genevo::random::Seed;
+use rayon::prelude::*;
+
+use super::config::{MigrationTopology, SolverConfig};
+use super::geneticalg::{Inputs, InputsPop, Simulator};
+use super::optimizer::seeded_rng;
+use super::progress::RunContext;
+use super::{evolve, extend_population, first_population, max_gene, next_horizon, trim_to_finish};
+
+/// Copies a genome without sharing its fitness cache, so extending one copy can't touch the other.
+fn copy_individual(individual: &InputsPop) -> InputsPop {
+    let fitness = *individual.1.lock().unwrap_or_else(PoisonError::into_inner);
+    InputsPop(individual.0.clone(), Arc::new(Mutex::new(fitness)))
+}
+
+/// Which islands send their best individuals to island `i`.
+fn sources(topology: MigrationTopology, i: usize, count: usize) -> Vec<usize> {
+    match topology {
+        MigrationTopology::Ring => vec![(i + count - 1) % count],
+        MigrationTopology::FullyConnected => (0..count).filter(|&j| j != i).collect(),
+    }
+}
+
+/// Replaces the worst individuals on every island with the best ones from its sources.
+fn migrate(simulator: &Simulator, config: &SolverConfig, islands: &mut [Vec<InputsPop>]) {
+    let count = islands.len();
+    for island in islands.iter_mut() {
+        island.sort_by_key(|individual| std::cmp::Reverse(simulator.fitness_of(individual)));
+    }
+    let incoming = (0..count)
+        .map(|i| {
+            let mut migrants = sources(config.migration_topology, i, count)
+                .into_iter()
+                .flat_map(|j| islands[j].iter().take(config.migrants))
+                .map(copy_individual)
+                .collect::<Vec<_>>();
+            migrants.sort_by_key(|individual| std::cmp::Reverse(simulator.fitness_of(individual)));
+            migrants.truncate(config.migrants);
+            migrants
+        })
+        .collect::<Vec<_>>();
+    for (island, migrants) in islands.iter_mut().zip(incoming) {
+        let keep = island.len() - migrants.len();
+        island.truncate(keep);
+        island.extend(migrants);
+    }
+}
+
+/// The GA, but with several populations evolving side by side and swapping their best individuals
+/// every `migration_interval` generations. Every island starts from `inputs` if there are any.
+pub(super) fn island_path(
+    simulator: &Simulator, config: &SolverConfig, ctx: &RunContext, inputs: Option<&Inputs>,
+) -> Inputs {
+    let max_gene = max_gene(config);
+    let mut seeds = seeded_rng(config);
+    let mut islands = (0..config.islands)
+        .map(|_| first_population(config, inputs, seeds.gen()))
+        .collect::<Vec<_>>();
+    let mut extended = inputs.is_some();
+    let best_of = |islands: &[Vec<InputsPop>]| {
+        islands
+            .iter()
+            .flatten()
+            .max_by_key(|individual| simulator.fitness_of(*individual))
+            .map(|individual| individual.0.clone())
+            .unwrap_or_default()
+    };
+    loop {
+        let mut remaining = config.generation_limit;
+        while remaining > 0 {
+            if ctx.cancelled() {
+                return best_of(&islands);
+            }
+            let generations = u64::min(config.migration_interval, remaining);
+            // drawn up front so the seeds don't depend on which thread gets there first
+            let island_seeds = islands.iter().map(|_| seeds.gen()).collect::<Vec<Seed>>();
+            let done = config.generation_limit - remaining;
+            islands = islands
+                .into_par_iter()
+                .zip(island_seeds)
+                .enumerate()
+                .map(|(index, (population, seed))| {
+                    evolve(
+                        simulator,
+                        config,
+                        population,
+                        extended,
+                        generations,
+                        seed,
+                        |generation, best, _| {
+                            // only the first island reports in between migrations, or they'd
+                            // all be talking over each other
+                            if index == 0 {
+                                ctx.report(simulator, done + generation, best);
+                            }
+                            !ctx.cancelled()
+                        },
+                    )
+                    .population
+                })
+                .collect();
+            migrate(simulator, config, &mut islands);
+            remaining -= generations;
+            ctx.report(
+                simulator,
+                config.generation_limit - remaining,
+                &best_of(&islands),
+            );
+        }
+        let best = best_of(&islands);
+        if simulator.check_if_hit_final(&best) {
+            return trim_to_finish(simulator, best);
+        }
+        let horizon = next_horizon(simulator, config, &best);
+        for island in islands.iter_mut() {
+            extend_population(island, horizon, max_gene, seeds.gen());
+        }
+        extended = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    #[test]
+    fn island_path_test() {
+        let (level, player, checkpoint) = Level::test_course();
+        let config = SolverConfig {
+            population_size: 12,
+            generation_limit: 4,
+            islands: 3,
+            migration_interval: 2,
+            seed: Some(5),
+            ..Default::default()
+        };
+        let simulator = Simulator::new(player, &level, vec![checkpoint]);
+        let ctx = RunContext::default();
+        let inputs = island_path(&simulator, &config, &ctx, None);
+        assert!(simulator.check_if_hit_final(&inputs));
+        assert_eq!(inputs, island_path(&simulator, &config, &ctx, None));
+    }
+}
@@ -0,0 +1,135 @@
+use genevo::random::{random_seed, Prng, Rng, SeedableRng};
+
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, Simulator};
+use super::progress::RunContext;
+use crate::player::DASH_OFFSET;
+
+/// How many frames the continuous optimizers start out searching over.
+const INITIAL_HORIZON: usize = 5;
+
+/// Anything that can find a path through every one of the simulator's checkpoints.
+/// If the run gets cancelled it should stop soon after and return the best path it has.
+pub(super) trait Optimizer {
+    fn optimize(&mut self, simulator: &mut Simulator, ctx: &RunContext) -> Inputs;
+}
+
+/// Runs `search` on a path that grows by a frame each time, warm starting it from the best path so far,
+/// until the final checkpoint gets hit or the path is `max_frames` long.
+pub(super) fn grow_horizon(
+    simulator: &Simulator, ctx: &RunContext, max_frames: usize,
+    mut search: impl FnMut(&Inputs) -> Inputs,
+) -> Inputs {
+    let mut best = vec![0f64; INITIAL_HORIZON];
+    loop {
+        best = search(&best);
+        if ctx.cancelled() || simulator.check_if_hit_final(&best) || best.len() >= max_frames {
+            return best;
+        }
+        best.push(best.last().copied().unwrap_or_default());
+    }
+}
+
+/// A generator seeded from the config, or from entropy if it doesn't have a seed.
+pub(super) fn seeded_rng(config: &SolverConfig) -> Prng {
+    match config.seed {
+        Some(seed) => Prng::seed_from_u64(seed),
+        None => Prng::from_seed(random_seed()),
+    }
+}
+
+/// The size of the space genes live in, past which they wrap back around to 0.
+pub(super) fn gene_range(config: &SolverConfig) -> f64 {
+    if config.allow_dashes {
+        DASH_OFFSET * 2f64
+    } else {
+        DASH_OFFSET
+    }
+}
+
+#[inline]
+pub(super) fn wrap_gene(gene: f64, range: f64) -> f64 {
+    let wrapped = gene.rem_euclid(range);
+    // rem_euclid can round up to range itself for tiny negative numbers
+    if wrapped >= range {
+        0f64
+    } else {
+        wrapped
+    }
+}
+
+/// The shortest signed distance from `from` to `to`, taking wrapping into account.
+#[inline]
+pub(super) fn gene_difference(to: f64, from: f64, range: f64) -> f64 {
+    let diff = (to - from).rem_euclid(range);
+    if diff > range / 2f64 {
+        diff - range
+    } else {
+        diff
+    }
+}
+
+pub(super) fn wrap_genes(genes: &[f64], range: f64) -> Inputs {
+    genes.iter().map(|&g| wrap_gene(g, range)).collect()
+}
+
+/// Samples the standard normal distribution with the Box-Muller transform.
+pub(super) fn gaussian<R: Rng>(rng: &mut R) -> f64 {
+    let u1 = 1f64 - rng.gen::<f64>();
+    let u2 = rng.gen::<f64>();
+    (-2f64 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::annealing::SimulatedAnnealing;
+    use crate::algorithm::beam::BeamSearch;
+    use crate::algorithm::cmaes::CmaEs;
+    use crate::algorithm::diffevo::DifferentialEvolution;
+    use crate::algorithm::greedy::Greedy;
+    use crate::colliders::Rect;
+    use crate::level::Level;
+
+    #[test]
+    fn optimizers_reach_checkpoint_test() {
+        let (level, player, checkpoint) = Level::test_course();
+        let config = SolverConfig {
+            population_size: 12,
+            generation_limit: 5,
+            ..Default::default()
+        };
+        let optimizers: [Box<dyn Optimizer>; 5] = [
+            Box::new(CmaEs::new(&config)),
+            Box::new(DifferentialEvolution::new(&config)),
+            Box::new(SimulatedAnnealing::new(&config)),
+            Box::new(BeamSearch::new(&config)),
+            Box::new(Greedy::new(&config)),
+        ];
+        for mut optimizer in optimizers {
+            let mut simulator = Simulator::new(player.clone(), &level, vec![checkpoint]);
+            let inputs = optimizer.optimize(&mut simulator, &RunContext::default());
+            assert!(simulator.check_if_hit_final(&inputs));
+        }
+    }
+
+    #[test]
+    fn grow_horizon_limit_test() {
+        let (level, player, _) = Level::test_course();
+        // way too far to get to in time, so it has to stop at the limit
+        let checkpoint = Rect::new_xywh(1000f32, -16f32, 4f32, 32f32);
+        let simulator = Simulator::new(player, &level, vec![checkpoint]);
+        let inputs = grow_horizon(&simulator, &RunContext::default(), 12, |start| {
+            start.clone()
+        });
+        assert_eq!(inputs.len(), 12);
+    }
+
+    #[test]
+    fn wrap_test() {
+        assert_eq!(wrap_gene(-1e-20, 360f64), 0f64);
+        assert_eq!(wrap_gene(370f64, 360f64), 10f64);
+        assert_eq!(gene_difference(10f64, 350f64, 360f64), 20f64);
+        assert_eq!(gene_difference(350f64, 10f64, 360f64), -20f64);
+    }
+}
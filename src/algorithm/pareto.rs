@@ -0,0 +1,308 @@
+use std::cmp::Ordering;
+
+use genevo::random::{Prng, Rng};
+use rayon::prelude::*;
+
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, Simulator};
+use super::optimizer::seeded_rng;
+use super::progress::{RunContext, SolverEvent};
+use super::{format_inputs, max_gene, population_from};
+use crate::player::Player;
+
+/// One path on the Pareto front, where nothing else is as fast while leaving with as much speed
+/// and as good a subpixel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParetoSolution {
+    pub frames: usize,
+    pub exit_speed: f64,
+    /// See [`exit_subpixel`].
+    pub exit_subpixel: f64,
+    /// The inputs up to the final checkpoint, formatted for CelesteTAS.
+    pub inputs: String,
+    pub(super) genome: Inputs,
+}
+
+/// How far through the current pixel the player is along whichever axis they're moving fastest on,
+/// in the direction they're moving, from 0 to 1. Further along means the next room's loading zone
+/// can get hit a frame earlier.
+fn exit_subpixel(player: &Player) -> f64 {
+    let (pos, speed) = (player.pos(), player.speed);
+    let (along, going_positive) = if speed.x.abs() >= speed.y.abs() {
+        (pos.x, speed.x >= 0f32)
+    } else {
+        (pos.y, speed.y >= 0f32)
+    };
+    let fraction = (along - along.floor()) as f64;
+    if going_positive {
+        fraction
+    } else {
+        1f64 - fraction
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Candidate {
+    genome: Inputs,
+    /// Frames, exit speed and exit subpixel, if the genome hits the final checkpoint at all.
+    finished: Option<(usize, f64, f64)>,
+    /// The plain fitness, which is all unfinished paths get compared by.
+    score: f64,
+}
+
+impl Candidate {
+    fn new(simulator: &Simulator, genome: Inputs) -> Self {
+        let finished = simulator.finish(&genome).map(|(frames, state)| {
+            (
+                frames,
+                state.player.speed.magnitude() as f64,
+                exit_subpixel(&state.player),
+            )
+        });
+        let score = simulator.fitness(&genome);
+        Self {
+            genome,
+            finished,
+            score,
+        }
+    }
+
+    /// Every objective, bigger being better.
+    fn objectives(&self) -> [f64; 3] {
+        match self.finished {
+            Some((frames, speed, subpixel)) => [-(frames as f64), speed, subpixel],
+            None => [self.score, 0f64, 0f64],
+        }
+    }
+
+    /// Finishing beats not finishing, then it's the usual Pareto dominance between finished paths
+    /// and just the fitness between unfinished ones.
+    fn dominates(&self, other: &Self) -> bool {
+        match (self.finished, other.finished) {
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => self.score > other.score,
+            (Some(_), Some(_)) => {
+                let (a, b) = (self.objectives(), other.objectives());
+                a.iter().zip(b.iter()).all(|(a, b)| a >= b)
+                    && a.iter().zip(b.iter()).any(|(a, b)| a > b)
+            }
+        }
+    }
+}
+
+/// Splits `candidates` into fronts, each one only dominated by those before it.
+fn non_dominated_sort(candidates: &[Candidate]) -> Vec<Vec<usize>> {
+    let count = candidates.len();
+    let mut dominated_by = vec![0usize; count];
+    let mut dominates = vec![Vec::new(); count];
+    for i in 0..count {
+        for j in 0..count {
+            if candidates[i].dominates(&candidates[j]) {
+                dominates[i].push(j);
+            } else if candidates[j].dominates(&candidates[i]) {
+                dominated_by[i] += 1;
+            }
+        }
+    }
+    let mut fronts = Vec::new();
+    let mut front = (0..count)
+        .filter(|&i| dominated_by[i] == 0)
+        .collect::<Vec<_>>();
+    while !front.is_empty() {
+        let mut next = Vec::new();
+        for &i in front.iter() {
+            for &j in dominates[i].iter() {
+                dominated_by[j] -= 1;
+                if dominated_by[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+/// How spread out each candidate in `front` is from its neighbours, with the ends of each
+/// objective always kept.
+fn crowding_distances(candidates: &[Candidate], front: &[usize]) -> Vec<f64> {
+    let mut distances = vec![0f64; front.len()];
+    for objective in 0..3 {
+        let value = |i: usize| candidates[front[i]].objectives()[objective];
+        let mut order = (0..front.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| value(a).partial_cmp(&value(b)).unwrap_or(Ordering::Equal));
+        let (Some(&first), Some(&last)) = (order.first(), order.last()) else {
+            continue;
+        };
+        let range = value(last) - value(first);
+        distances[first] = f64::INFINITY;
+        distances[last] = f64::INFINITY;
+        if range <= 0f64 {
+            continue;
+        }
+        for window in order.windows(3) {
+            distances[window[1]] += (value(window[2]) - value(window[0])) / range;
+        }
+    }
+    distances
+}
+
+/// The best `size` candidates by front, then by crowding distance within the last front that fits,
+/// along with the rank and crowding distance of each.
+fn survivors(candidates: Vec<Candidate>, size: usize) -> Vec<(Candidate, usize, f64)> {
+    let mut chosen = Vec::with_capacity(size);
+    for (rank, front) in non_dominated_sort(&candidates).into_iter().enumerate() {
+        let distances = crowding_distances(&candidates, &front);
+        let mut members = front.into_iter().zip(distances).collect::<Vec<_>>();
+        members.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        for (i, distance) in members {
+            if chosen.len() == size {
+                return chosen;
+            }
+            chosen.push((candidates[i].clone(), rank, distance));
+        }
+    }
+    chosen
+}
+
+/// Binary tournament on rank, then crowding distance.
+fn tournament<'a>(population: &'a [(Candidate, usize, f64)], rng: &mut Prng) -> &'a Inputs {
+    let a = &population[rng.gen_range(0..population.len())];
+    let b = &population[rng.gen_range(0..population.len())];
+    let a_wins = a.1 < b.1 || (a.1 == b.1 && a.2 >= b.2);
+    if a_wins {
+        &a.0.genome
+    } else {
+        &b.0.genome
+    }
+}
+
+/// NSGA-II on paths `pareto_extra_frames` longer than `inputs`, trading frames for exit speed and
+/// subpixel. Gives back the finished paths on the first front, fewest frames first.
+pub(super) fn pareto_front(
+    simulator: &Simulator, config: &SolverConfig, inputs: &Inputs, ctx: &RunContext,
+) -> Vec<ParetoSolution> {
+    let max_gene = max_gene(config);
+    let mut rng = seeded_rng(config);
+    let mut padded = inputs.clone();
+    let last = padded.last().copied().unwrap_or_default();
+    padded.extend(std::iter::repeat_n(last, config.pareto_extra_frames));
+    let genomes = population_from(&padded, config, rng.gen())
+        .into_iter()
+        .map(|p| p.0)
+        .collect::<Vec<_>>();
+    let mut population = survivors(evaluate(simulator, genomes), config.population_size);
+    for generation in 0..config.pareto_generations {
+        if ctx.cancelled() {
+            break;
+        }
+        let children = (0..config.population_size)
+            .map(|_| {
+                let (a, b) = (
+                    tournament(&population, &mut rng),
+                    tournament(&population, &mut rng),
+                );
+                let cut = rng.gen_range(0..=a.len());
+                a[..cut]
+                    .iter()
+                    .chain(b[cut..].iter())
+                    .map(|&gene| {
+                        if rng.gen_bool(config.mutation_rate) {
+                            rng.gen_range(0f64..=max_gene)
+                        } else {
+                            gene
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut candidates = population
+            .into_iter()
+            .map(|(candidate, _, _)| candidate)
+            .collect::<Vec<_>>();
+        candidates.extend(evaluate(simulator, children));
+        population = survivors(candidates, config.population_size);
+        if let Some(fastest) = fastest(&population) {
+            ctx.report(simulator, generation, &fastest.genome);
+        }
+    }
+    let mut front = population
+        .iter()
+        .filter(|(_, rank, _)| *rank == 0)
+        .filter_map(|(candidate, _, _)| {
+            let (frames, exit_speed, exit_subpixel) = candidate.finished?;
+            let mut genome = candidate.genome[..frames].to_vec();
+            // the children only get constrained as they're simulated, so fix them up to match
+            simulator.constrain(&mut genome);
+            Some(ParetoSolution {
+                frames,
+                exit_speed,
+                exit_subpixel,
+                inputs: format_inputs(genome.clone()),
+                genome,
+            })
+        })
+        .collect::<Vec<_>>();
+    front.sort_by(|a, b| {
+        (a.frames, -a.exit_speed, -a.exit_subpixel)
+            .partial_cmp(&(b.frames, -b.exit_speed, -b.exit_subpixel))
+            .unwrap_or(Ordering::Equal)
+    });
+    // paths that only differ in ways that don't change where they end up all tie on the front
+    front.dedup_by(|a, b| {
+        (a.frames, a.exit_speed, a.exit_subpixel) == (b.frames, b.exit_speed, b.exit_subpixel)
+    });
+    ctx.send(SolverEvent::Front(front.clone()));
+    front
+}
+
+fn evaluate(simulator: &Simulator, genomes: Vec<Inputs>) -> Vec<Candidate> {
+    genomes
+        .into_par_iter()
+        .map(|genome| Candidate::new(simulator, genome))
+        .collect()
+}
+
+fn fastest(population: &[(Candidate, usize, f64)]) -> Option<&Candidate> {
+    population
+        .iter()
+        .filter_map(|(candidate, _, _)| candidate.finished.map(|(frames, ..)| (frames, candidate)))
+        .min_by_key(|(frames, _)| *frames)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use genevo::random::SeedableRng;
+
+    fn finished(frames: usize, speed: f64, subpixel: f64) -> Candidate {
+        Candidate {
+            genome: Inputs::new(),
+            finished: Some((frames, speed, subpixel)),
+            score: 0f64,
+        }
+    }
+
+    #[test]
+    fn non_dominated_sort_test() {
+        let candidates = vec![
+            finished(10, 50f64, 0.5),
+            finished(12, 90f64, 0.5),
+            finished(12, 40f64, 0.2),
+            Candidate {
+                genome: Inputs::new(),
+                finished: None,
+                score: 100f64,
+            },
+        ];
+        let fronts = non_dominated_sort(&candidates);
+        assert_eq!(fronts, vec![vec![0, 1], vec![2], vec![3]]);
+        let chosen = survivors(candidates, 2);
+        assert!(chosen.iter().all(|(_, rank, _)| *rank == 0));
+        let mut rng = Prng::seed_from_u64(1);
+        assert!(tournament(&chosen, &mut rng).is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+
+use colored::Colorize;
+use genevo::random::Prng;
+
+use super::config::SolverConfig;
+use super::fitness::FitnessBreakdown;
+use super::geneticalg::{Genome, Inputs, Simulator};
+use super::pareto::ParetoSolution;
+use super::savestate::{RunInfo, SolverState};
+use super::AlgorithmError;
+
+/// A snapshot of how a run is going.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub generation: u64,
+    /// How many frames long the paths being searched are.
+    pub horizon: usize,
+    pub best_fitness: f64,
+    /// How many checkpoints the best path so far hits.
+    pub checkpoint_index: usize,
+}
+
+#[derive(Debug)]
+pub enum SolverEvent {
+    Progress(Progress),
+    /// How many frames shortening the path cut out of it.
+    Shortened(usize),
+    /// Sent just before finishing when the run was looking for a Pareto front.
+    Front(Vec<ParetoSolution>),
+    Finished(Result<FitnessBreakdown, AlgorithmError>),
+}
+
+/// What a run needs to talk to whoever started it.
+#[derive(Debug, Default)]
+pub struct RunContext {
+    events: Option<Sender<SolverEvent>>,
+    cancel: Arc<AtomicBool>,
+    save: Option<(PathBuf, RunInfo)>,
+}
+
+impl RunContext {
+    pub fn new(events: Sender<SolverEvent>, cancel: Arc<AtomicBool>) -> Self {
+        Self {
+            events: Some(events),
+            cancel,
+            save: None,
+        }
+    }
+
+    /// Saves the GA's state to `path` after every generation and every time its genomes get longer.
+    /// Only the GA with a single population can save, anything else fails to start.
+    pub fn with_save(mut self, path: PathBuf, info: RunInfo) -> Self {
+        self.save = Some((path, info));
+        self
+    }
+
+    /// Whether the run should stop and hand back the best it has so far.
+    pub fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn send(&self, event: SolverEvent) {
+        if let Some(events) = &self.events {
+            // the receiver going away just means nobody's listening anymore
+            let _ = events.send(event);
+        }
+    }
+
+    pub(super) fn saving(&self) -> bool {
+        self.save.is_some()
+    }
+
+    pub(super) fn save<G: Genome>(
+        &self, simulator: &Simulator, config: &SolverConfig, population: &[G], generation: u64,
+        rng: &Prng,
+    ) {
+        let Some((path, info)) = &self.save else {
+            return;
+        };
+        let population = population.iter().map(G::to_inputs).collect::<Vec<_>>();
+        let state = SolverState::new(simulator, config, &population, generation, rng, info);
+        if let Err(e) = state.save(path) {
+            println!("{}", e.to_string().red());
+        }
+    }
+
+    pub(super) fn report(&self, simulator: &Simulator, generation: u64, best: &Inputs) {
+        if self.events.is_none() {
+            return;
+        }
+        let state = simulator.sim_player(best);
+        self.send(SolverEvent::Progress(Progress {
+            generation,
+            horizon: best.len(),
+            best_fitness: simulator.score(&state),
+            checkpoint_index: state.checkpoint_index,
+        }));
+    }
+}
+
+/// A run going on in the background.
+pub struct RunHandle {
+    pub events: Receiver<SolverEvent>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl RunHandle {
+    pub fn new(events: Receiver<SolverEvent>, cancel: Arc<AtomicBool>) -> Self {
+        Self { events, cancel }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn cancel_test() {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let ctx = RunContext::new(sender, cancel.clone());
+        let handle = RunHandle::new(receiver, cancel);
+        assert!(!ctx.cancelled());
+        handle.cancel();
+        assert!(ctx.cancelled());
+        ctx.send(SolverEvent::Finished(Ok(FitnessBreakdown::default())));
+        assert!(matches!(
+            handle.events.try_recv(),
+            Ok(SolverEvent::Finished(Ok(_)))
+        ));
+    }
+}
@@ -0,0 +1,100 @@
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, Simulator};
+use super::optimizer::wrap_gene;
+use super::progress::RunContext;
+use super::waterspeed::swim_pass;
+use crate::player::DASH_OFFSET;
+
+/// Passes stop once the bracket around each angle is narrower than this, in degrees.
+pub(super) const MIN_STEP: f64 = 0.001;
+
+const INV_GOLDEN_RATIO: f64 = 0.618_033_988_749_895;
+
+/// Swaps the angle of `gene` out for `angle` without touching whether it dashes.
+#[inline]
+pub(super) fn with_angle(gene: f64, angle: f64) -> f64 {
+    let base = if gene >= DASH_OFFSET {
+        DASH_OFFSET
+    } else {
+        0f64
+    };
+    base + wrap_gene(angle, DASH_OFFSET)
+}
+
+/// Golden-section search for the best angle for one frame within `step` degrees of where it is now.
+pub(super) fn search_frame(
+    simulator: &Simulator, inputs: &mut Inputs, frame: usize, step: f64,
+) -> f64 {
+    let gene = inputs[frame];
+    let centre = gene % DASH_OFFSET;
+    let mut eval = |angle: f64| {
+        inputs[frame] = with_angle(gene, angle);
+        simulator.fitness(inputs)
+    };
+    let (mut lo, mut hi) = (centre - step, centre + step);
+    let mut a = hi - INV_GOLDEN_RATIO * (hi - lo);
+    let mut b = lo + INV_GOLDEN_RATIO * (hi - lo);
+    let (mut fa, mut fb) = (eval(a), eval(b));
+    while hi - lo > MIN_STEP {
+        if fa >= fb {
+            hi = b;
+            (b, fb) = (a, fa);
+            a = hi - INV_GOLDEN_RATIO * (hi - lo);
+            fa = eval(a);
+        } else {
+            lo = a;
+            (a, fa) = (b, fb);
+            b = lo + INV_GOLDEN_RATIO * (hi - lo);
+            fb = eval(b);
+        }
+    }
+    inputs[frame] = gene;
+    with_angle(gene, if fa >= fb { a } else { b })
+}
+
+/// Coordinate descent over every frame's angle, halving the search bracket after each pass,
+/// starting from a pass of the swim model's angles. A change only sticks if the path still hits
+/// every checkpoint and the fitness goes up.
+pub(super) fn refine(
+    simulator: &Simulator, inputs: Inputs, config: &SolverConfig, ctx: &RunContext,
+) -> Inputs {
+    let mut inputs = swim_pass(simulator, inputs, ctx);
+    let mut best_fitness = simulator.fitness(&inputs);
+    let mut step = config.refine_step;
+    while step >= MIN_STEP {
+        for frame in 0..inputs.len() {
+            if ctx.cancelled() {
+                return inputs;
+            }
+            let candidate = search_frame(simulator, &mut inputs, frame, step);
+            let old = std::mem::replace(&mut inputs[frame], candidate);
+            let fitness = simulator.fitness(&inputs);
+            if fitness > best_fitness && simulator.check_if_hit_final(&inputs) {
+                best_fitness = fitness;
+            } else {
+                inputs[frame] = old;
+            }
+        }
+        step /= 2f64;
+    }
+    inputs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    #[test]
+    fn refine_test() {
+        let (level, player, checkpoint) = Level::test_course();
+        let simulator = Simulator::new(player, &level, vec![checkpoint]);
+        let inputs = vec![120f64; 12];
+        assert!(simulator.check_if_hit_final(&inputs));
+        let config = SolverConfig::default();
+        let refined = refine(&simulator, inputs.clone(), &config, &RunContext::default());
+        assert!(simulator.check_if_hit_final(&refined));
+        assert!(simulator.fitness(&refined) > simulator.fitness(&inputs));
+        assert_eq!(with_angle(400f64, 370f64), 370f64);
+    }
+}
@@ -0,0 +1,225 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use genevo::random::Prng;
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, InputsPop, OrdFloat64, Simulator};
+use crate::player::Player;
+
+#[derive(Error, Debug)]
+pub enum SaveError {
+    #[error("Couldn't access the save file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Couldn't read the save file: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("{0} has changed since the run was saved")]
+    LevelChanged(String),
+}
+
+/// FNV-1a, which unlike the standard library's hashers is the same on every build.
+fn level_hash(text: &[u8]) -> u64 {
+    text.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedIndividual {
+    pub genome: Inputs,
+    pub fitness: Option<f64>,
+}
+
+/// Everything needed to pick a GA run back up where it left off.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SolverState {
+    /// Always has the seed filled in, even if the run was started without one.
+    pub config: SolverConfig,
+    /// The level's info file, which gets loaded again on resume rather than whatever's open then.
+    pub info_path: String,
+    /// A hash of what was in the info file, since CelesteTAS writes over it with whichever room
+    /// it's in.
+    pub level_hash: u64,
+    /// Where the player started, so the population gets replayed from the same place.
+    pub player: Player,
+    pub checkpoints: String,
+    pub pickups: String,
+    pub base_checkpoint: usize,
+    pub base_frame: usize,
+    /// How many generations have already run at this horizon.
+    pub generation: u64,
+    pub population: Vec<SavedIndividual>,
+    /// Where the seeds for each block of generations come from, as it was after drawing the seed
+    /// for the block in progress. The GA's own randomness within a block isn't saved, so a resumed
+    /// run goes its own way rather than repeating what the same seed would've done uninterrupted.
+    pub rng: Prng,
+}
+
+impl SolverState {
+    pub(super) fn new(
+        simulator: &Simulator, config: &SolverConfig, population: &[InputsPop], generation: u64,
+        rng: &Prng, info: &RunInfo,
+    ) -> Self {
+        let (base_checkpoint, base_frame) = simulator.base();
+        Self {
+            config: config.clone(),
+            info_path: info.info_path.clone(),
+            level_hash: info.level_hash,
+            player: simulator.player().clone(),
+            checkpoints: info.checkpoints.clone(),
+            pickups: info.pickups.clone(),
+            base_checkpoint,
+            base_frame,
+            generation,
+            population: population
+                .iter()
+                .map(|p| SavedIndividual {
+                    genome: p.0.clone(),
+                    fitness: p
+                        .1
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .map(|f| f.0.into_inner()),
+                })
+                .collect(),
+            rng: rng.clone(),
+        }
+    }
+
+    pub(super) fn population(&self) -> Vec<InputsPop> {
+        self.population
+            .iter()
+            .map(|p| {
+                let fitness = p.fitness.map(|f| OrdFloat64(OrderedFloat(f)));
+                InputsPop(p.genome.clone(), Arc::new(Mutex::new(fitness)))
+            })
+            .collect()
+    }
+
+    /// Writes next to `path` first and then moves it over, so getting killed partway through
+    /// leaves the last save as it was rather than half a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        std::fs::write(&temp, serde_json::to_string(self)?)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Makes sure the info file still holds the level the run was saved in.
+    pub fn check_level(&self) -> Result<(), SaveError> {
+        if level_hash(&std::fs::read(&self.info_path)?) != self.level_hash {
+            return Err(SaveError::LevelChanged(self.info_path.clone()));
+        }
+        Ok(())
+    }
+}
+
+/// The level and checkpoints a run was started with, which get saved alongside the GA's own state.
+#[derive(Clone, Debug)]
+pub struct RunInfo {
+    pub info_path: String,
+    pub level_hash: u64,
+    pub checkpoints: String,
+    pub pickups: String,
+}
+
+impl RunInfo {
+    /// Reads the info file so a resume can tell whether it's been written over since.
+    pub fn new(info_path: &str, checkpoints: &str, pickups: &str) -> Result<Self, SaveError> {
+        Ok(Self {
+            info_path: info_path.to_string(),
+            level_hash: level_hash(&std::fs::read(info_path)?),
+            checkpoints: checkpoints.to_string(),
+            pickups: pickups.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+    use genevo::random::{Rng, SeedableRng};
+
+    #[test]
+    fn save_load_test() {
+        let mut rng = Prng::seed_from_u64(3);
+        rng.gen::<u64>();
+        let state = SolverState {
+            config: SolverConfig::default(),
+            info_path: String::from("level.txt"),
+            level_hash: 0,
+            player: Player::new(Point::new(0f32, 0f32), Point::new(12f32, -5.5)),
+            checkpoints: String::from("0, 0, 8, 8"),
+            pickups: String::new(),
+            base_checkpoint: 0,
+            base_frame: 0,
+            generation: 7,
+            population: vec![SavedIndividual {
+                genome: vec![1.5, 359.25],
+                fitness: Some(-12.5),
+            }],
+            rng,
+        };
+        let path = std::env::temp_dir().join("mauna_kea_save_load_test.json");
+        if let Err(e) = state.save(&path) {
+            panic!("{e}");
+        }
+        let loaded = match SolverState::load(&path) {
+            Ok(loaded) => loaded,
+            Err(e) => panic!("{e}"),
+        };
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.config, state.config);
+        assert_eq!(loaded.population, state.population);
+        assert_eq!(loaded.player.pos(), state.player.pos());
+        assert_eq!(loaded.generation, 7);
+        let (mut a, mut b) = (loaded.rng, state.rng);
+        assert_eq!(a.gen::<u64>(), b.gen::<u64>());
+    }
+
+    #[test]
+    fn level_changed_test() {
+        let path = std::env::temp_dir().join("mauna_kea_level_changed_test.txt");
+        let info_path = path.to_string_lossy().to_string();
+        let write = |text: &str| {
+            if let Err(e) = std::fs::write(&path, text) {
+                panic!("{e}");
+            }
+        };
+        write("Pos: 0, 0");
+        let info = match RunInfo::new(&info_path, "", "") {
+            Ok(info) => info,
+            Err(e) => panic!("{e}"),
+        };
+        let state = SolverState {
+            config: SolverConfig::default(),
+            info_path: info.info_path,
+            level_hash: info.level_hash,
+            player: Player::new(Point::new(0f32, 0f32), Point::new(0f32, 0f32)),
+            checkpoints: info.checkpoints,
+            pickups: info.pickups,
+            base_checkpoint: 0,
+            base_frame: 0,
+            generation: 0,
+            population: vec![],
+            rng: Prng::seed_from_u64(3),
+        };
+        assert!(state.check_level().is_ok());
+        write("Pos: 8, 0");
+        let changed = state.check_level();
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(changed, Err(SaveError::LevelChanged(_))));
+    }
+}
@@ -0,0 +1,360 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use genevo::genetic::{Children, Parents};
+use genevo::operator::{CrossoverOp, GeneticOperator, MutationOp};
+use genevo::prelude::*;
+use ordered_float::OrderedFloat;
+
+use super::config::SolverConfig;
+use super::constraints::AngleConstraints;
+use super::geneticalg::{Constrained, Genome, Inputs, InputsPop, OrdFloat64, Simulator};
+use super::max_gene;
+
+/// A run of frames that all hold the same gene, like one line of a TAS.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Segment {
+    pub angle: f64,
+    pub frames: usize,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct SegmentsPop(pub Vec<Segment>, pub Arc<Mutex<Option<OrdFloat64>>>);
+
+impl SegmentsPop {
+    fn new(segments: Vec<Segment>) -> Self {
+        Self(segments, Arc::new(Mutex::new(None)))
+    }
+}
+
+impl PartialEq for SegmentsPop {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Genotype for SegmentsPop {
+    type Dna = Segment;
+}
+
+/// Squashes every run of the same gene in `inputs` into one segment.
+pub(super) fn segments_of(inputs: &Inputs) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    for &gene in inputs {
+        match segments.last_mut() {
+            Some(last) if last.angle == gene => last.frames += 1,
+            _ => segments.push(Segment {
+                angle: gene,
+                frames: 1,
+            }),
+        }
+    }
+    segments
+}
+
+/// One gene per frame again, for the simulator.
+pub(super) fn expand(segments: &[Segment]) -> Inputs {
+    segments
+        .iter()
+        .flat_map(|segment| std::iter::repeat_n(segment.angle, segment.frames))
+        .collect()
+}
+
+fn frames_in(segments: &[Segment]) -> usize {
+    segments.iter().map(|segment| segment.frames).sum()
+}
+
+/// Cuts the segments at `frame`, splitting whichever segment it lands in two.
+fn split_at(segments: &[Segment], frame: usize) -> (Vec<Segment>, Vec<Segment>) {
+    let (mut head, mut tail) = (Vec::new(), Vec::new());
+    let mut start = 0;
+    for &segment in segments {
+        let end = start + segment.frames;
+        if end <= frame {
+            head.push(segment);
+        } else if start >= frame {
+            tail.push(segment);
+        } else {
+            head.push(Segment {
+                frames: frame - start,
+                ..segment
+            });
+            tail.push(Segment {
+                frames: end - frame,
+                ..segment
+            });
+        }
+        start = end;
+    }
+    (head, tail)
+}
+
+/// Cuts every parent at the same random frame and puts its start onto the next parent's end.
+/// Cutting by frame rather than by segment keeps both halves lined up with when they happen.
+#[derive(Clone, Debug)]
+pub(super) struct SegmentCrossBreeder;
+
+impl GeneticOperator for SegmentCrossBreeder {
+    fn name() -> String {
+        "Segment-Cross-Breeder".to_string()
+    }
+}
+
+impl CrossoverOp<SegmentsPop> for SegmentCrossBreeder {
+    fn crossover<R>(&self, parents: Parents<SegmentsPop>, rng: &mut R) -> Children<SegmentsPop>
+    where
+        R: Rng + Sized,
+    {
+        let shortest = parents.iter().map(|p| frames_in(&p.0)).min().unwrap_or(0);
+        let cut = rng.gen_range(0..=shortest);
+        (0..parents.len())
+            .map(|i| {
+                let (mut head, _) = split_at(&parents[i].0, cut);
+                let (_, tail) = split_at(&parents[(i + 1) % parents.len()].0, cut);
+                head.extend(tail);
+                SegmentsPop::new(head)
+            })
+            .collect()
+    }
+}
+
+/// Gives every segment a `mutation_rate` chance of getting a new angle, being split in two with a
+/// new angle for the second half, being merged into the next one, or moving its boundary with the
+/// next one by a frame. None of these change how many frames the path is in total, since fewer
+/// frames always scores better until the final checkpoint gets hit and the path would just shrink.
+#[derive(Clone, Debug)]
+pub(super) struct SegmentMutator {
+    pub mutation_rate: f64,
+    pub max_gene: f64,
+}
+
+impl GeneticOperator for SegmentMutator {
+    fn name() -> String {
+        "Segment-Mutation".to_string()
+    }
+}
+
+impl MutationOp<SegmentsPop> for SegmentMutator {
+    fn mutate<R>(&self, genome: SegmentsPop, rng: &mut R) -> SegmentsPop
+    where
+        R: Rng + Sized,
+    {
+        let mut segments = genome.0;
+        let mut i = 0;
+        while i < segments.len() {
+            if !rng.gen_bool(self.mutation_rate) {
+                i += 1;
+                continue;
+            }
+            match rng.gen_range(0..4) {
+                0 => segments[i].angle = rng.gen_range(0f64..=self.max_gene),
+                1 if segments[i].frames > 1 => {
+                    let first = rng.gen_range(1..segments[i].frames);
+                    let second = Segment {
+                        angle: rng.gen_range(0f64..=self.max_gene),
+                        frames: segments[i].frames - first,
+                    };
+                    segments[i].frames = first;
+                    segments.insert(i + 1, second);
+                    // skip the new half so it doesn't get mutated twice
+                    i += 1;
+                }
+                2 if i + 1 < segments.len() => {
+                    let next = segments.remove(i + 1);
+                    segments[i].frames += next.frames;
+                }
+                3 if i + 1 < segments.len() => {
+                    // take a frame from whichever side can spare one
+                    let (from, to) = if rng.gen_bool(0.5) {
+                        (i + 1, i)
+                    } else {
+                        (i, i + 1)
+                    };
+                    if segments[from].frames > 1 {
+                        segments[from].frames -= 1;
+                        segments[to].frames += 1;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        SegmentsPop::new(segments)
+    }
+}
+
+pub(super) struct SegmentsBuilder {
+    pub max_gene: f64,
+    /// How many frames the segments add up to.
+    pub frames: usize,
+    pub angles: AngleConstraints,
+}
+
+impl GenomeBuilder<SegmentsPop> for SegmentsBuilder {
+    fn build_genome<R>(&self, _index: usize, rng: &mut R) -> SegmentsPop
+    where
+        R: Rng + Sized,
+    {
+        let mut segments = Vec::new();
+        let mut left = self.frames;
+        while left > 0 {
+            let frames = rng.gen_range(1..=left);
+            segments.push(Segment {
+                angle: rng.gen_range(0f64..=self.max_gene),
+                frames,
+            });
+            left -= frames;
+        }
+        let mut genome = SegmentsPop::new(segments);
+        genome.constrain(&self.angles);
+        genome
+    }
+}
+
+impl FitnessFunction<SegmentsPop, OrdFloat64> for Simulator<'_> {
+    fn fitness_of(&self, inp: &SegmentsPop) -> OrdFloat64 {
+        let mut fitness = inp.1.lock().unwrap_or_else(PoisonError::into_inner);
+        if fitness.is_none() {
+            *fitness = Some(OrdFloat64(OrderedFloat(self.fitness(&expand(&inp.0)))));
+        }
+        fitness.unwrap()
+    }
+
+    fn average(&self, a: &[OrdFloat64]) -> OrdFloat64 {
+        FitnessFunction::<InputsPop, OrdFloat64>::average(self, a)
+    }
+
+    fn highest_possible_fitness(&self) -> OrdFloat64 {
+        FitnessFunction::<InputsPop, OrdFloat64>::highest_possible_fitness(self)
+    }
+
+    fn lowest_possible_fitness(&self) -> OrdFloat64 {
+        FitnessFunction::<InputsPop, OrdFloat64>::lowest_possible_fitness(self)
+    }
+}
+
+impl Genome for SegmentsPop {
+    type Builder = SegmentsBuilder;
+    type Crossover = Constrained<SegmentCrossBreeder>;
+    type Mutation<'a> = Constrained<SegmentMutator>;
+
+    fn builder(config: &SolverConfig, frames: usize) -> Self::Builder {
+        SegmentsBuilder {
+            max_gene: max_gene(config),
+            frames,
+            angles: config.angles.clone(),
+        }
+    }
+
+    fn crossover(config: &SolverConfig) -> Self::Crossover {
+        Constrained {
+            inner: SegmentCrossBreeder,
+            angles: config.angles.clone(),
+        }
+    }
+
+    fn mutation<'a>(
+        _simulator: &Simulator<'a>, config: &SolverConfig, mutation_rate: f64,
+    ) -> Self::Mutation<'a> {
+        Constrained {
+            inner: SegmentMutator {
+                mutation_rate,
+                max_gene: max_gene(config),
+            },
+            angles: config.angles.clone(),
+        }
+    }
+
+    fn from_inputs(individual: InputsPop) -> Self {
+        Self(segments_of(&individual.0), individual.1)
+    }
+
+    fn to_inputs(&self) -> InputsPop {
+        InputsPop(expand(&self.0), self.1.clone())
+    }
+
+    fn frames(&self) -> usize {
+        frames_in(&self.0)
+    }
+
+    fn extend(&mut self, frames: usize, gene: f64) {
+        let left = frames.saturating_sub(self.frames());
+        if left > 0 {
+            self.0.push(Segment {
+                angle: gene,
+                frames: left,
+            });
+        }
+        self.1 = Arc::new(Mutex::new(None));
+    }
+
+    /// Constraints can pin single frames, so this goes through one gene per frame and squashes the
+    /// result back down.
+    fn constrain(&mut self, angles: &AngleConstraints) {
+        if angles.is_empty() {
+            return;
+        }
+        let mut inputs = expand(&self.0);
+        angles.apply(&mut inputs);
+        self.0 = segments_of(&inputs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::constraints::FixedAngle;
+    use genevo::random::{Prng, SeedableRng};
+
+    #[test]
+    fn segments_test() {
+        let inputs = vec![4.2, 99.3, 99.3, 99.3, 55.9, 55.9];
+        let segments = segments_of(&inputs);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(expand(&segments), inputs);
+        let (head, tail) = split_at(&segments, 2);
+        assert_eq!(expand(&head), vec![4.2, 99.3]);
+        assert_eq!(expand(&tail), vec![99.3, 99.3, 55.9, 55.9]);
+
+        let mut rng = Prng::seed_from_u64(9);
+        let mutator = SegmentMutator {
+            mutation_rate: 1f64,
+            max_gene: 360f64,
+        };
+        let mut genome = SegmentsPop::new(segments);
+        for _ in 0..100 {
+            genome = mutator.mutate(genome, &mut rng);
+            assert_eq!(frames_in(&genome.0), inputs.len());
+            assert!(genome.0.iter().all(|segment| segment.frames > 0));
+        }
+        let parents = vec![genome.clone(), SegmentsPop::new(segments_of(&inputs))];
+        let children = SegmentCrossBreeder.crossover(parents, &mut rng);
+        assert_eq!(children.len(), 2);
+        assert!(children
+            .iter()
+            .all(|child| frames_in(&child.0) == inputs.len()));
+
+        // the constrained operators keep the pinned frames even when they land inside a segment
+        let config = SolverConfig {
+            angles: AngleConstraints {
+                fixed: vec![FixedAngle {
+                    start: 2,
+                    end: 4,
+                    angle: 180f64,
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mutator = Constrained {
+            inner: mutator,
+            angles: config.angles.clone(),
+        };
+        let builder = SegmentsPop::builder(&config, inputs.len());
+        for i in 0..20 {
+            let genome = mutator.mutate(builder.build_genome(i, &mut rng), &mut rng);
+            let frames = expand(&genome.0);
+            assert_eq!(frames.len(), inputs.len());
+            assert_eq!(frames[2..4], [180f64, 180f64]);
+        }
+    }
+}
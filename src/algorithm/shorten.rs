@@ -0,0 +1,106 @@
+use rayon::prelude::*;
+
+use super::config::SolverConfig;
+use super::geneticalg::{Inputs, Simulator};
+use super::optimizer::gene_difference;
+use super::progress::RunContext;
+use super::refine::{search_frame, with_angle};
+use crate::player::DASH_OFFSET;
+
+/// How many frames either side of a removed one get re-optimised to make up for it.
+const REPAIR_RADIUS: usize = 2;
+
+/// Drops everything after the frame the final checkpoint gets hit on.
+fn trim(simulator: &Simulator, mut inputs: Inputs) -> Inputs {
+    if let Some(frames) = simulator.frames_to_finish(&inputs) {
+        inputs.truncate(frames);
+    }
+    inputs
+}
+
+/// Every way of getting rid of `frame`: deleting it outright, or merging it into the next frame
+/// by averaging their angles.
+fn removals(inputs: &Inputs, frame: usize) -> Vec<Inputs> {
+    let mut deleted = inputs.clone();
+    deleted.remove(frame);
+    let mut out = vec![deleted];
+    if let Some(&next) = inputs.get(frame + 1) {
+        let this = inputs[frame];
+        if (this >= DASH_OFFSET) == (next >= DASH_OFFSET) {
+            let (a, b) = (this % DASH_OFFSET, next % DASH_OFFSET);
+            let mut merged = inputs.clone();
+            merged.remove(frame);
+            merged[frame] = with_angle(next, a + gene_difference(b, a, DASH_OFFSET) / 2f64);
+            out.push(merged);
+        }
+    }
+    out
+}
+
+/// Re-optimises the frames around `frame` one at a time until the path finishes again, if it can.
+fn repair(
+    simulator: &Simulator, mut inputs: Inputs, frame: usize, config: &SolverConfig,
+) -> Option<Inputs> {
+    if simulator.check_if_hit_final(&inputs) {
+        return Some(inputs);
+    }
+    let end = usize::min(frame + REPAIR_RADIUS + 1, inputs.len());
+    for i in frame.saturating_sub(REPAIR_RADIUS)..end {
+        inputs[i] = search_frame(simulator, &mut inputs, i, config.refine_step);
+        if simulator.check_if_hit_final(&inputs) {
+            return Some(inputs);
+        }
+    }
+    None
+}
+
+/// Keeps removing frames until the path can't finish in any fewer.
+pub(super) fn shorten(
+    simulator: &Simulator, inputs: Inputs, config: &SolverConfig, ctx: &RunContext,
+) -> Inputs {
+    let mut inputs = trim(simulator, inputs);
+    loop {
+        if ctx.cancelled() {
+            return inputs;
+        }
+        // find_map_first keeps this deterministic despite running in parallel
+        let shorter = (0..inputs.len()).into_par_iter().find_map_first(|frame| {
+            removals(&inputs, frame)
+                .into_iter()
+                .find_map(|candidate| repair(simulator, candidate, frame, config))
+        });
+        match shorter {
+            Some(shorter) => inputs = trim(simulator, shorter),
+            None => return inputs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    #[test]
+    fn shorten_test() {
+        let (level, player, checkpoint) = Level::test_course();
+        let simulator = Simulator::new(player, &level, vec![checkpoint]);
+        // swims the wrong way for a bit, and carries on well past the checkpoint
+        let mut inputs = vec![270f64; 3];
+        inputs.extend([120f64; 20]);
+        let Some(before) = simulator.frames_to_finish(&inputs) else {
+            panic!("inputs should reach the checkpoint");
+        };
+        let shortened = shorten(
+            &simulator,
+            inputs,
+            &SolverConfig::default(),
+            &RunContext::default(),
+        );
+        assert_eq!(
+            simulator.frames_to_finish(&shortened),
+            Some(shortened.len())
+        );
+        assert!(shortened.len() < before);
+    }
+}
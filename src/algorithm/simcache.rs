@@ -0,0 +1,125 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use super::geneticalg::{Inputs, SimState};
+
+/// Where a snapshot is: how many genes it's after and a hash of them.
+pub(super) type PrefixKey = (usize, u64);
+
+/// A state partway through a genome, along with the genes that got it there so a hash collision
+/// can't hand back the wrong one.
+#[derive(Debug)]
+struct Snapshot {
+    /// Every snapshot from the same genome shares these, and only the first `length` of them count.
+    genes: Arc<[f64]>,
+    state: SimState,
+}
+
+/// Snapshots of the simulation partway through genomes, so genomes that start the same way only
+/// get simulated once up to where they split. A snapshot gets taken every `interval` frames and
+/// keyed by how many genes are before it and a hash of them.
+#[derive(Debug)]
+pub(super) struct SimCache {
+    interval: usize,
+    /// How many snapshots to keep before starting over with none.
+    capacity: usize,
+    states: RwLock<HashMap<PrefixKey, Snapshot>>,
+    /// How many simulations got to skip ahead to a snapshot.
+    hits: AtomicUsize,
+}
+
+impl SimCache {
+    pub fn new(interval: usize, capacity: usize) -> Self {
+        Self {
+            interval,
+            capacity,
+            states: RwLock::new(HashMap::new()),
+            hits: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[cfg(test)]
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The key of every prefix of `inputs` that would have a snapshot, shortest first.
+    pub fn prefix_keys(&self, inputs: &Inputs) -> Vec<PrefixKey> {
+        let mut hasher = DefaultHasher::new();
+        let mut keys = Vec::with_capacity(inputs.len() / self.interval);
+        for (i, gene) in inputs.iter().enumerate() {
+            hasher.write_u64(gene.to_bits());
+            if (i + 1) % self.interval == 0 {
+                keys.push((i + 1, hasher.finish()));
+            }
+        }
+        keys
+    }
+
+    /// The furthest along snapshot of `inputs` out of `keys`, along with how many frames it's after.
+    pub fn resume(&self, inputs: &Inputs, keys: &[PrefixKey]) -> Option<(usize, SimState)> {
+        let states = self.states.read().unwrap_or_else(PoisonError::into_inner);
+        let found = keys.iter().rev().find_map(|key @ &(length, _)| {
+            let snapshot = states.get(key)?;
+            (snapshot.genes[..length] == inputs[..length]).then(|| (length, snapshot.state.clone()))
+        });
+        if found.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
+
+    /// Keeps `snapshots`, which all have to be from partway through `inputs`.
+    pub fn store(&self, inputs: &Inputs, snapshots: Vec<(PrefixKey, SimState)>) {
+        let Some(&((longest, _), _)) = snapshots.last() else {
+            return;
+        };
+        let genes: Arc<[f64]> = inputs[..longest].into();
+        let mut states = self.states.write().unwrap_or_else(PoisonError::into_inner);
+        // the GA keeps moving on to longer paths, so old snapshots are rarely worth keeping around
+        if states.len() + snapshots.len() > self.capacity {
+            states.clear();
+        }
+        states.extend(snapshots.into_iter().map(|(key, state)| {
+            let genes = genes.clone();
+            (key, Snapshot { genes, state })
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::geneticalg::Simulator;
+    use crate::colliders::Rect;
+    use crate::level::Level;
+
+    #[test]
+    fn cached_sim_test() {
+        let (level, player, _) = Level::test_course();
+        let checkpoints = vec![Rect::new_xywh(40f32, -16f32, 4f32, 32f32)];
+        let plain = Simulator::new(player.clone(), &level, checkpoints.clone());
+        let cached = Simulator::new(player, &level, checkpoints).with_cache(3, 4);
+        let mut inputs = vec![90f64; 10];
+        for _ in 0..3 {
+            assert_eq!(
+                cached.fitness_breakdown(&inputs),
+                plain.fitness_breakdown(&inputs)
+            );
+            // same start, different end, so it picks up from partway through
+            inputs[8] = 45f64;
+            inputs.push(135f64);
+        }
+        assert_eq!(cached.cache().map(|cache| cache.hits()), Some(2));
+    }
+}
@@ -1,7 +1,208 @@
-// TODO: Fill this out more
-pub fn weight_angle(angle: &f32) -> (f32, f32) {
-    (1f32, 1f32)
+use std::sync::{Arc, Mutex};
+
+use genevo::operator::{GeneticOperator, MutationOp};
+use genevo::prelude::*;
+
+use super::geneticalg::{Inputs, InputsPop, SimState, Simulator};
+use super::progress::RunContext;
+use super::refine::with_angle;
+use crate::player::{Player, SWIM_MAX_X, SWIM_MAX_Y};
+use crate::point::Point;
+
+/// How far apart the angles the inverse model tries first are, in degrees.
+const COARSE_STEP: f64 = 1f64;
+
+/// The inverse model stops narrowing down once it's within this many degrees.
+const FINE_STEP: f64 = 0.001;
+
+const INV_GOLDEN_RATIO: f64 = 0.618_033_988_749_895;
+
+/// How much each degree off the wanted direction counts against an angle, for when the speed
+/// comes out the same.
+const TIE_BREAK: f64 = 1e-6;
+
+/// The speed after one frame of swimming at `angle`, ignoring retained speed from walls.
+pub(super) fn next_speed(speed: Point, angle: f64, delta_time: f32) -> Point {
+    let target = Player::swim_target(angle);
+    Point::new(
+        Player::swim_axis(speed.x, target.x, SWIM_MAX_X, delta_time),
+        Player::swim_axis(speed.y, target.y, SWIM_MAX_Y, delta_time),
+    )
 }
 
+/// The angle that scores highest given the speed it leads to. Tries every degree, then
+/// golden-section searches around the best one.
+fn best_angle(speed: Point, delta_time: f32, score: impl Fn(f64, Point) -> f64) -> f64 {
+    let eval = |angle: f64| {
+        let angle = angle.rem_euclid(360f64);
+        score(angle, next_speed(speed, angle, delta_time))
+    };
+    let (coarse, coarse_score) = (0..(360f64 / COARSE_STEP) as usize)
+        .map(|i| {
+            let angle = i as f64 * COARSE_STEP;
+            (angle, eval(angle))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or_default();
+    let (mut lo, mut hi) = (coarse - COARSE_STEP, coarse + COARSE_STEP);
+    let mut a = hi - INV_GOLDEN_RATIO * (hi - lo);
+    let mut b = lo + INV_GOLDEN_RATIO * (hi - lo);
+    let (mut fa, mut fb) = (eval(a), eval(b));
+    while hi - lo > FINE_STEP {
+        if fa >= fb {
+            hi = b;
+            (b, fb) = (a, fa);
+            a = hi - INV_GOLDEN_RATIO * (hi - lo);
+            fa = eval(a);
+        } else {
+            lo = a;
+            (a, fa) = (b, fb);
+            b = lo + INV_GOLDEN_RATIO * (hi - lo);
+            fb = eval(b);
+        }
+    }
+    let best = [(coarse, coarse_score), (a, fa), (b, fb)]
+        .into_iter()
+        .max_by(|x, y| x.1.total_cmp(&y.1))
+        .map_or(coarse, |(angle, _)| angle);
+    best.rem_euclid(360f64) % 360f64
+}
+
+/// The angle that gets the speed after one frame closest to `desired`.
 #[cfg(test)]
-mod tests {}
+fn angle_for_velocity(speed: Point, desired: Point, delta_time: f32) -> f64 {
+    best_angle(speed, delta_time, |_, next| {
+        -(next - desired).magnitude() as f64
+    })
+}
+
+/// The angle that leaves the player moving fastest along `direction` after one frame. Every angle
+/// that gets there equally fast also speeds up along `direction` equally, unless it's held closer
+/// to it, which keeps accelerating for longer.
+pub(super) fn angle_toward(speed: Point, direction: Point, delta_time: f32) -> f64 {
+    let wanted = (direction.x as f64).atan2(-direction.y as f64).to_degrees();
+    best_angle(speed, delta_time, |angle, next| {
+        let off = (angle - wanted + 180f64).rem_euclid(360f64) - 180f64;
+        next.dot(direction) as f64 - TIE_BREAK * off.abs()
+    })
+}
+
+/// The model's angle for the frame after `state`, heading for the next checkpoint.
+fn model_angle(simulator: &Simulator, state: &SimState) -> Option<f64> {
+    let direction = simulator.target_direction(state)?;
+    Some(angle_toward(
+        state.player.speed,
+        direction,
+        state.player.timing.delta_time(),
+    ))
+}
+
+/// Like genevo's `RandomValueMutator`, but a `bias` share of the mutated genes get the angle the
+/// swim model says heads for the next checkpoint fastest instead of a random one. Finding those
+/// means simulating the genome up to the last one, so it's slower the higher `bias` is.
+#[derive(Clone, Debug)]
+pub(super) struct SwimMutator<'a> {
+    pub simulator: Simulator<'a>,
+    pub mutation_rate: f64,
+    pub max_gene: f64,
+    pub bias: f64,
+}
+
+impl GeneticOperator for SwimMutator<'_> {
+    fn name() -> String {
+        "Swim-Biased-Mutation".to_string()
+    }
+}
+
+impl MutationOp<InputsPop> for SwimMutator<'_> {
+    fn mutate<R>(&self, genome: InputsPop, rng: &mut R) -> InputsPop
+    where
+        R: Rng + Sized,
+    {
+        let mut genes = genome.0;
+        // Some(true) for the genes the model picks, Some(false) for random ones
+        let picks = genes
+            .iter()
+            .map(|_| {
+                rng.gen_bool(self.mutation_rate)
+                    .then(|| rng.gen_bool(self.bias))
+            })
+            .collect::<Vec<_>>();
+        let modelled = picks.iter().rposition(|&pick| pick == Some(true));
+        let mut state = self.simulator.initial_state();
+        let mut simulating = modelled.is_some();
+        for (frame, (gene, pick)) in genes.iter_mut().zip(picks).enumerate() {
+            let model = match pick {
+                Some(true) if simulating => model_angle(&self.simulator, &state),
+                _ => None,
+            };
+            match (pick, model) {
+                (_, Some(angle)) => *gene = with_angle(*gene, angle),
+                (Some(_), None) => *gene = rng.gen_range(0f64..=self.max_gene),
+                (None, None) => {}
+            }
+            if simulating {
+                simulating = Some(frame) != modelled && self.simulator.step(&mut state, *gene);
+            }
+        }
+        InputsPop(genes, Arc::new(Mutex::new(None)))
+    }
+}
+
+/// Tries each frame on the model's angle for heading to the next checkpoint, keeping it if the
+/// path gets better and still finishes. It's one fitness check per frame against a whole search
+/// in [`super::refine::refine`], so it gets the angles close before the slower passes start.
+pub(super) fn swim_pass(simulator: &Simulator, mut inputs: Inputs, ctx: &RunContext) -> Inputs {
+    let mut best_fitness = simulator.fitness(&inputs);
+    let mut state = simulator.initial_state();
+    for frame in 0..inputs.len() {
+        if ctx.cancelled() {
+            break;
+        }
+        if let Some(angle) = model_angle(simulator, &state) {
+            let candidate = with_angle(inputs[frame], angle);
+            let old = std::mem::replace(&mut inputs[frame], candidate);
+            let fitness = simulator.fitness(&inputs);
+            if fitness > best_fitness && simulator.check_if_hit_final(&inputs) {
+                best_fitness = fitness;
+            } else {
+                inputs[frame] = old;
+            }
+        }
+        if !simulator.step(&mut state, inputs[frame]) {
+            break;
+        }
+    }
+    inputs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colliders::Rect;
+    use crate::level::Level;
+    use crate::timing::RAW_DELTA_TIME;
+
+    #[test]
+    fn swim_model_test() {
+        let level = Level::open_water(Rect::new_xywh(-64f32, -64f32, 128f32, 128f32));
+        let mut player = Player::new(Point::new(75f32, -20f32), Point::new(0f32, 0f32));
+        for angle in [37.5f64, 200f64, 90f64, 359f64] {
+            let predicted = next_speed(player.speed, angle, RAW_DELTA_TIME);
+            player.speed_calc(angle, &level);
+            assert_eq!(predicted, player.speed);
+        }
+
+        let speed = Point::new(20f32, 30f32);
+        let desired = next_speed(speed, 123.4, RAW_DELTA_TIME);
+        let angle = angle_for_velocity(speed, desired, RAW_DELTA_TIME);
+        assert!((next_speed(speed, angle, RAW_DELTA_TIME) - desired).magnitude() < 0.01);
+        // from a standstill the fastest way right is straight right
+        let right = angle_toward(
+            Point::new(0f32, 0f32),
+            Point::new(1f32, 0f32),
+            RAW_DELTA_TIME,
+        );
+        assert!((right - 90f64).abs() < 0.01);
+    }
+}
@@ -1,9 +1,8 @@
 use rstar::{RTreeObject, AABB};
+use serde::{Deserialize, Serialize};
 
 use crate::point::Point;
 
-const DELTATIME: f32 = 0.0166667;
-
 #[derive(Clone, Copy, Default, Debug)]
 pub enum Direction {
     #[default]
@@ -18,7 +17,7 @@ pub enum Axes {
     Vertical,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Collider {
     Rectangular(Rect),
     Circular(Circle),
@@ -42,14 +41,14 @@ impl Collider {
     pub fn move_collider(&mut self, x: f32, y: f32) {
         match self {
             Collider::Rectangular(rect) => {
-                rect.ul.x += x * DELTATIME;
-                rect.ul.y += y * DELTATIME;
-                rect.dr.x += x * DELTATIME;
-                rect.dr.y += y * DELTATIME;
+                rect.ul.x += x;
+                rect.ul.y += y;
+                rect.dr.x += x;
+                rect.dr.y += y;
             }
             Collider::Circular(circ) => {
-                circ.origin.x += x * DELTATIME;
-                circ.origin.y += y * DELTATIME;
+                circ.origin.x += x;
+                circ.origin.y += y;
             }
         };
     }
@@ -113,7 +112,7 @@ impl RTreeObject for Collider {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Rect {
     pub ul: Point,
     pub ur: Point,
@@ -203,7 +202,7 @@ impl Rect {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Circle {
     pub radius: f32,
     pub origin: Point,
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+use crate::colliders::{Circle, Collider, Rect};
+use crate::point::Point;
+
+const PUFFER_DETECT_RADIUS: f32 = 32f32;
+const PUFFER_PUSH_RADIUS: f32 = 40f32;
+const PUFFER_HIT_COOLDOWN: f32 = 0.1f32;
+const PUFFER_RESPAWN_TIME: f32 = 2.5f32;
+const BUMPER_RADIUS: f32 = 12f32;
+const BUMPER_RESPAWN_TIME: f32 = 0.6f32;
+pub const SUPER_BOUNCE_SPEED: f32 = -185f32;
+pub const SIDE_BOUNCE_SPEED: f32 = 240f32;
+pub const SIDE_BOUNCE_UP_SPEED: f32 = -140f32;
+const REFILL_RESPAWN_TIME: f32 = 2.5f32;
+
+// NOTE: puffers bob around their spawn point in game, that movement isnt simulated yet
+#[derive(Clone, Copy, Debug)]
+pub struct Puffer {
+    pub pos: Point,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum PufferState {
+    #[default]
+    Idle,
+    /// The player is close, but above it, so it puffs up instead of exploding.
+    Alert,
+    /// Exploded this frame.
+    Exploding,
+    /// Can't be touched again until the timer runs out.
+    Cooldown(f32),
+}
+
+impl Puffer {
+    pub fn new(pos: Point) -> Self {
+        Self { pos }
+    }
+
+    pub fn hitbox(&self) -> Collider {
+        Collider::Rectangular(Rect::new_xywh(
+            self.pos.x - 6f32,
+            self.pos.y - 5f32,
+            12f32,
+            10f32,
+        ))
+    }
+
+    pub fn detect_radius(&self) -> Collider {
+        Collider::Circular(Circle::new(PUFFER_DETECT_RADIUS, self.pos))
+    }
+
+    pub fn push_radius(&self) -> Collider {
+        Collider::Circular(Circle::new(PUFFER_PUSH_RADIUS, self.pos))
+    }
+
+    /// Works out the puffer's next state given where the player is this frame.
+    pub fn update(&self, state: PufferState, hitbox: &Collider, delta_time: f32) -> PufferState {
+        let (bottom, center_y) = match hitbox.rect() {
+            Some(rect) => (rect.dr.y + 1f32, rect.center().y),
+            None => unreachable!(),
+        };
+        match state {
+            PufferState::Exploding => PufferState::Cooldown(PUFFER_RESPAWN_TIME),
+            PufferState::Cooldown(timer) if timer - delta_time > 0f32 => {
+                PufferState::Cooldown(timer - delta_time)
+            }
+            PufferState::Cooldown(_) => PufferState::Idle,
+            PufferState::Idle | PufferState::Alert => {
+                if hitbox.collide_check(&self.hitbox()) {
+                    if bottom > self.pos.y + 3f32 {
+                        PufferState::Exploding
+                    } else {
+                        PufferState::Cooldown(PUFFER_HIT_COOLDOWN)
+                    }
+                } else if hitbox.collide_check(&self.detect_radius()) {
+                    if center_y >= self.pos.y + 1f32 {
+                        PufferState::Exploding
+                    } else {
+                        PufferState::Alert
+                    }
+                } else {
+                    PufferState::Idle
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpringOrientation {
+    Floor,
+    /// Attached to a wall on its left, so it pushes the player right.
+    WallLeft,
+    /// Attached to a wall on its right, so it pushes the player left.
+    WallRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VelocityKind {
+    Bumper,
+    Spring(SpringOrientation),
+}
+
+/// Anything that sets the player's speed outright when touched.
+#[derive(Clone, Copy, Debug)]
+pub struct VelocityEntity {
+    pub kind: VelocityKind,
+    pub pos: Point,
+}
+
+impl VelocityEntity {
+    pub fn new(kind: VelocityKind, pos: Point) -> Self {
+        Self { kind, pos }
+    }
+
+    pub fn collider(&self) -> Collider {
+        let (x, y) = (self.pos.x, self.pos.y);
+        match self.kind {
+            VelocityKind::Bumper => Collider::Circular(Circle::new(BUMPER_RADIUS, self.pos)),
+            VelocityKind::Spring(SpringOrientation::Floor) => {
+                Collider::Rectangular(Rect::new_xywh(x - 8f32, y - 6f32, 16f32, 6f32))
+            }
+            VelocityKind::Spring(SpringOrientation::WallLeft) => {
+                Collider::Rectangular(Rect::new_xywh(x, y - 8f32, 6f32, 16f32))
+            }
+            VelocityKind::Spring(SpringOrientation::WallRight) => {
+                Collider::Rectangular(Rect::new_xywh(x - 6f32, y - 8f32, 6f32, 16f32))
+            }
+        }
+    }
+
+    /// How long it stays unusable after being hit. Springs can be reused straight away.
+    pub fn respawn_time(&self) -> f32 {
+        match self.kind {
+            VelocityKind::Bumper => BUMPER_RESPAWN_TIME,
+            VelocityKind::Spring(_) => 0f32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PickupKind {
+    Strawberry,
+    Key,
+    Refill,
+}
+
+/// Something the player can collect by touching it.
+#[derive(Clone, Copy, Debug)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub pos: Point,
+}
+
+impl Pickup {
+    pub fn new(kind: PickupKind, pos: Point) -> Self {
+        Self { kind, pos }
+    }
+
+    pub fn hitbox(&self) -> Rect {
+        let size = match self.kind {
+            PickupKind::Strawberry => 14f32,
+            PickupKind::Key => 12f32,
+            PickupKind::Refill => 16f32,
+        };
+        Rect::new_xywh(
+            self.pos.x - size / 2f32,
+            self.pos.y - size / 2f32,
+            size,
+            size,
+        )
+    }
+
+    /// Only refills come back, everything else stays collected.
+    pub fn respawn_time(&self) -> Option<f32> {
+        match self.kind {
+            PickupKind::Refill => Some(REFILL_RESPAWN_TIME),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {}
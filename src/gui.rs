@@ -1,6 +1,16 @@
+use std::path::PathBuf;
+use std::sync::mpsc::TryRecvError;
+
+use arboard::Clipboard;
 use colored::Colorize;
 
 use crate::algorithm;
+use crate::algorithm::config::{GenomeKind, MigrationTopology, OptimizerKind, SolverConfig};
+use crate::algorithm::constraints::FixedAngle;
+use crate::algorithm::fitness::FitnessBreakdown;
+use crate::algorithm::pareto::ParetoSolution;
+use crate::algorithm::progress::{Progress, RunHandle, SolverEvent};
+use crate::algorithm::savestate::{RunInfo, SolverState};
 use crate::level::Level;
 use regex::Regex;
 
@@ -8,12 +18,27 @@ pub struct Options {
     pub decimals: i32,
     pub info_path: String,
     pub checkpoints: String,
+    pub pickups: String,
+    pub config_path: String,
+    pub solver: SolverConfig,
+    pub limit_refills: bool,
+    pub refills: u32,
+    pub state_path: String,
+    pub save_state: bool,
+    pub tas_path: String,
 }
 
 pub struct MaunaKea {
     options: Options,
     template: String,
     old_template: String,
+    pickup_list: Vec<String>,
+    fitness: Option<FitnessBreakdown>,
+    run: Option<RunHandle>,
+    progress: Option<Progress>,
+    /// How many frames the last run's shortening pass saved.
+    shortened: Option<usize>,
+    front: Vec<ParetoSolution>,
 }
 
 impl Default for MaunaKea {
@@ -22,7 +47,15 @@ impl Default for MaunaKea {
             options: Options {
                 decimals: 3,
                 info_path: String::from(""),
-                checkpoints: String::from("")
+                checkpoints: String::from(""),
+                pickups: String::from(""),
+                config_path: String::from(""),
+                solver: SolverConfig::default(),
+                limit_refills: false,
+                refills: 0,
+                state_path: String::from(""),
+                save_state: false,
+                tas_path: String::from(""),
             },
             // TODO: deal with bubble columns and wind later
             // TODO: add custom water shit later, cant be bothered to rn
@@ -57,16 +90,442 @@ impl Default for MaunaKea {
 
             "WaterUL: {Water.TopLeft} " +
             "WaterDR: {Water.BottomRight} " +
+            "PufferPos: {Puffer.Position} " +
+            "BumperPos: {Bumper.Position} " +
+            "SpringPos: {Spring.Position} " +
+            "SpringOrientation: {Spring.Orientation} " +
+            "StrawberryPos: {Strawberry.Position} " +
+            "KeyPos: {Key.Position} " +
+            "RefillPos: {Refill.Position} " +
+
+            "Dashes: {Player.Dashes} " +
+            "MaxDashes: {Player.MaxDashes} " +
+            "TimeRate: {Engine.TimeRate} " +
+            "FreezeTimer: {Engine.FreezeTimer} " +
         
             "Bounds: {Level.Bounds} " +
             "Solids: {Level.Session.LevelData.Solids}",
             old_template: String::from(""),
+            pickup_list: vec![],
+            fitness: None,
+            run: None,
+            progress: None,
+            shortened: None,
+            front: Vec::new(),
+        }
+    }
+}
+
+/// A checkbox for whether `value` is set at all, and what it's set to if it is.
+fn optional_value(ui: &mut egui::Ui, value: &mut Option<f64>, name: &str) {
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+        ui.checkbox(&mut enabled, name);
+        if enabled {
+            ui.add(egui::DragValue::new(value.get_or_insert(0f64)));
+        } else {
+            *value = None;
+        }
+    });
+}
+
+/// Like [`optional_value`], for a range between 0 and 1.
+fn optional_range(ui: &mut egui::Ui, range: &mut Option<[f64; 2]>, name: &str) {
+    ui.horizontal(|ui| {
+        let mut enabled = range.is_some();
+        ui.checkbox(&mut enabled, name);
+        if enabled {
+            let [low, high] = range.get_or_insert([0f64, 1f64]);
+            ui.add(
+                egui::DragValue::new(low)
+                    .clamp_range(0f64..=1f64)
+                    .speed(0.01),
+            );
+            ui.label("to");
+            ui.add(
+                egui::DragValue::new(high)
+                    .clamp_range(0f64..=1f64)
+                    .speed(0.01),
+            );
+        } else {
+            *range = None;
+        }
+    });
+}
+
+impl MaunaKea {
+    fn save_path(&self) -> Option<PathBuf> {
+        self.options
+            .save_state
+            .then(|| PathBuf::from(&self.options.state_path))
+    }
+
+    /// Takes in everything the running solver has sent since the last frame.
+    fn poll_run(&mut self) {
+        let Some(run) = &self.run else {
+            return;
+        };
+        loop {
+            let event = match run.events.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    println!("{}", "The solver stopped without finishing".red());
+                    self.run = None;
+                    return;
+                }
+            };
+            match event {
+                SolverEvent::Progress(progress) => self.progress = Some(progress),
+                SolverEvent::Shortened(frames) => self.shortened = Some(frames),
+                SolverEvent::Front(front) => self.front = front,
+                SolverEvent::Finished(result) => {
+                    match result {
+                        Ok(breakdown) => self.fitness = Some(breakdown),
+                        Err(e) => println!("{}", e.to_string().red()),
+                    }
+                    self.run = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn solver_options(&mut self, ui: &mut egui::Ui) {
+        let solver = &mut self.options.solver;
+        egui::ComboBox::from_label("Optimizer")
+            .selected_text(solver.optimizer.name())
+            .show_ui(ui, |ui| {
+                for kind in OptimizerKind::ALL {
+                    ui.selectable_value(&mut solver.optimizer, kind, kind.name());
+                }
+            });
+        ui.add(
+            egui::DragValue::new(&mut solver.population_size)
+                .clamp_range(2..=10000)
+                .prefix("Population: "),
+        );
+        ui.add(
+            egui::DragValue::new(&mut solver.selection_ratio)
+                .clamp_range(0f64..=1f64)
+                .prefix("Selection ratio: ")
+                .speed(0.01),
+        );
+        ui.add(
+            egui::DragValue::new(&mut solver.individuals_per_parents)
+                .clamp_range(2..=100)
+                .prefix("Individuals per parents: "),
+        );
+        ui.add(
+            egui::DragValue::new(&mut solver.initial_mutation_rate)
+                .clamp_range(0f64..=1f64)
+                .prefix("Initial mutation rate: ")
+                .speed(0.01),
+        );
+        ui.add(
+            egui::DragValue::new(&mut solver.mutation_rate)
+                .clamp_range(0f64..=1f64)
+                .prefix("Mutation rate: ")
+                .speed(0.01),
+        );
+        ui.add(
+            egui::DragValue::new(&mut solver.reinsertion_ratio)
+                .clamp_range(0f64..=1f64)
+                .prefix("Reinsertion ratio: ")
+                .speed(0.01),
+        );
+        ui.add(
+            egui::DragValue::new(&mut solver.swim_bias)
+                .clamp_range(0f64..=1f64)
+                .prefix("Swim bias: ")
+                .speed(0.01),
+        );
+        ui.add(
+            egui::DragValue::new(&mut solver.generation_limit)
+                .clamp_range(1..=10000)
+                .prefix("Generations per extension: "),
+        );
+        ui.add(
+            egui::DragValue::new(&mut solver.horizon_jump)
+                .clamp_range(0f64..=10f64)
+                .prefix("Horizon jump: ")
+                .speed(0.01),
+        );
+        match solver.optimizer {
+            OptimizerKind::Genetic => {
+                egui::ComboBox::from_label("Genome")
+                    .selected_text(solver.genome.name())
+                    .show_ui(ui, |ui| {
+                        for genome in GenomeKind::ALL {
+                            ui.selectable_value(&mut solver.genome, genome, genome.name());
+                        }
+                    });
+                ui.checkbox(&mut solver.greedy_seed, "Start from the greedy path");
+                ui.add(
+                    egui::DragValue::new(&mut solver.islands)
+                        .clamp_range(1..=256)
+                        .prefix("Islands: "),
+                );
+                ui.add_enabled_ui(solver.islands > 1, |ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut solver.migration_interval)
+                            .clamp_range(1..=10000)
+                            .prefix("Migration interval: "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut solver.migrants)
+                            .clamp_range(0..=10000)
+                            .prefix("Migrants: "),
+                    );
+                    egui::ComboBox::from_label("Topology")
+                        .selected_text(solver.migration_topology.name())
+                        .show_ui(ui, |ui| {
+                            for topology in MigrationTopology::ALL {
+                                ui.selectable_value(
+                                    &mut solver.migration_topology,
+                                    topology,
+                                    topology.name(),
+                                );
+                            }
+                        });
+                });
+            }
+            OptimizerKind::CmaEs => {
+                ui.add(
+                    egui::DragValue::new(&mut solver.cmaes_sigma)
+                        .clamp_range(0.001f64..=360f64)
+                        .prefix("Sigma: "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut solver.search_max_frames)
+                        .clamp_range(1..=100000)
+                        .prefix("Max frames: "),
+                );
+            }
+            OptimizerKind::DifferentialEvolution => {
+                ui.add(
+                    egui::DragValue::new(&mut solver.de_differential_weight)
+                        .clamp_range(0.01f64..=2f64)
+                        .prefix("Differential weight: ")
+                        .speed(0.01),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut solver.de_crossover_rate)
+                        .clamp_range(0f64..=1f64)
+                        .prefix("Crossover rate: ")
+                        .speed(0.01),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut solver.search_max_frames)
+                        .clamp_range(1..=100000)
+                        .prefix("Max frames: "),
+                );
+            }
+            OptimizerKind::SimulatedAnnealing => {
+                ui.add(
+                    egui::DragValue::new(&mut solver.annealing_temperature)
+                        .clamp_range(0.001f64..=100000f64)
+                        .prefix("Temperature: "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut solver.annealing_step)
+                        .clamp_range(0.001f64..=360f64)
+                        .prefix("Step: "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut solver.search_max_frames)
+                        .clamp_range(1..=100000)
+                        .prefix("Max frames: "),
+                );
+            }
+            OptimizerKind::BeamSearch => {
+                ui.add(
+                    egui::DragValue::new(&mut solver.beam_width)
+                        .clamp_range(1..=100000)
+                        .prefix("Beam width: "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut solver.beam_angles)
+                        .clamp_range(1..=3600)
+                        .prefix("Angles per frame: "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut solver.beam_max_frames)
+                        .clamp_range(1..=100000)
+                        .prefix("Max frames: "),
+                );
+            }
+            OptimizerKind::Greedy => {
+                ui.add(
+                    egui::DragValue::new(&mut solver.greedy_max_frames)
+                        .clamp_range(1..=100000)
+                        .prefix("Max frames: "),
+                );
+            }
+        }
+        let mut fixed_seed = solver.seed.is_some();
+        ui.checkbox(&mut fixed_seed, "Fixed seed");
+        if fixed_seed {
+            let seed = solver.seed.get_or_insert(0);
+            ui.add(egui::DragValue::new(seed).prefix("Seed: "));
+        } else {
+            solver.seed = None;
+        }
+        ui.checkbox(&mut solver.shorten, "Remove frames");
+        ui.checkbox(&mut solver.pareto, "Pareto front of frames vs exit state");
+        if solver.pareto {
+            ui.add(
+                egui::DragValue::new(&mut solver.pareto_generations)
+                    .clamp_range(1..=100000)
+                    .prefix("Pareto generations: "),
+            );
+            ui.add(
+                egui::DragValue::new(&mut solver.pareto_extra_frames)
+                    .clamp_range(0..=1000)
+                    .prefix("Extra frames allowed: "),
+            );
+        }
+        ui.checkbox(&mut solver.refine, "Refine result");
+        if solver.refine {
+            ui.add(
+                egui::DragValue::new(&mut solver.refine_step)
+                    .clamp_range(0.001f64..=180f64)
+                    .prefix("Refinement step: "),
+            );
+        }
+        ui.add(
+            egui::DragValue::new(&mut solver.cache_interval)
+                .clamp_range(0..=1000)
+                .prefix("Frames between cached states: "),
+        );
+        if solver.cache_interval > 0 {
+            ui.add(
+                egui::DragValue::new(&mut solver.cache_size)
+                    .clamp_range(1..=10000000)
+                    .prefix("Cached states kept: "),
+            );
+        }
+        ui.collapsing("Fitness weights", |ui| {
+            let weights = &mut solver.fitness;
+            for (weight, name) in [
+                (&mut weights.checkpoint, "Checkpoint: "),
+                (&mut weights.frames, "Frames: "),
+                (&mut weights.unfinished_frames, "Unfinished frames: "),
+                (&mut weights.closest_approach, "Closest approach: "),
+                (&mut weights.closest_frame, "Frame of closest approach: "),
+                (&mut weights.final_distance, "Final distance: "),
+                (&mut weights.end_speed, "End speed: "),
+                (&mut weights.exit, "Exit constraints: "),
+            ] {
+                ui.add(
+                    egui::DragValue::new(weight)
+                        .clamp_range(0f64..=f64::MAX)
+                        .prefix(name)
+                        .speed(0.1),
+                );
+            }
+        });
+        ui.collapsing("Exit constraints", |ui| {
+            let exit = &mut solver.exit;
+            for (value, name) in [
+                (&mut exit.min_speed_x, "Min horizontal speed"),
+                (&mut exit.max_speed_x, "Max horizontal speed"),
+                (&mut exit.min_speed_y, "Min vertical speed"),
+                (&mut exit.max_speed_y, "Max vertical speed"),
+                (&mut exit.direction, "Direction"),
+            ] {
+                optional_value(ui, value, name);
+            }
+            if exit.direction.is_some() {
+                ui.add(
+                    egui::DragValue::new(&mut exit.direction_tolerance)
+                        .clamp_range(0f64..=180f64)
+                        .prefix("Direction tolerance: "),
+                );
+            }
+            for (range, name) in [
+                (&mut exit.subpixel_x, "Horizontal subpixel"),
+                (&mut exit.subpixel_y, "Vertical subpixel"),
+            ] {
+                optional_range(ui, range, name);
+            }
+            ui.checkbox(
+                &mut exit.hard,
+                "Paths breaking these don't count as finished",
+            );
+        });
+        ui.collapsing("Angle constraints", |ui| {
+            let angles = &mut solver.angles;
+            ui.label("Allowed ranges, clockwise:");
+            let mut remove = None;
+            for (i, [from, to]) in angles.allowed.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(from).clamp_range(0f64..=360f64));
+                    ui.label("to");
+                    ui.add(egui::DragValue::new(to).clamp_range(0f64..=360f64));
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                angles.allowed.remove(i);
+            }
+            if ui.button("Add range").clicked() {
+                angles.allowed.push([0f64, 360f64]);
+            }
+            ui.label("Fixed angles, from the first frame up to the last:");
+            let mut remove = None;
+            for (i, fixed) in angles.fixed.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut fixed.start).prefix("Frames: "));
+                    ui.label("to");
+                    ui.add(egui::DragValue::new(&mut fixed.end));
+                    ui.add(
+                        egui::DragValue::new(&mut fixed.angle)
+                            .clamp_range(0f64..=360f64)
+                            .prefix("Angle: "),
+                    );
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                angles.fixed.remove(i);
+            }
+            if ui.button("Add fixed angle").clicked() {
+                angles.fixed.push(FixedAngle {
+                    start: 0,
+                    end: 1,
+                    angle: 0f64,
+                });
+            }
+            optional_value(ui, &mut angles.max_change, "Max change per frame");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Config path: ");
+            ui.text_edit_singleline(&mut self.options.config_path);
+        });
+        if ui.button("Load Config").clicked() {
+            match SolverConfig::load(&self.options.config_path) {
+                Ok(config) => {
+                    self.options.solver = config;
+                    println!("{}", "Loaded the solver config!".bright_green());
+                }
+                Err(e) => println!("{}", e.to_string().red()),
+            }
         }
     }
 }
 
 impl eframe::App for MaunaKea {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_run();
+        if self.run.is_some() {
+            // keep redrawing so progress shows up without having to move the mouse
+            ctx.request_repaint();
+        }
         egui::SidePanel::right("options")
             .resizable(false)
             .default_width(0.3)
@@ -77,7 +536,18 @@ impl eframe::App for MaunaKea {
                         .prefix("Decimals: ")
                         .speed(0.05),
                 );
-                ui.label("test!");
+                ui.checkbox(&mut self.options.solver.allow_dashes, "Allow dashes");
+                ui.add_enabled_ui(self.options.solver.allow_dashes, |ui| {
+                    ui.checkbox(&mut self.options.limit_refills, "Limit dash refills");
+                    ui.add_enabled(
+                        self.options.limit_refills,
+                        egui::DragValue::new(&mut self.options.refills)
+                            .prefix("Refills: ")
+                            .speed(0.05),
+                    );
+                });
+                ui.separator();
+                self.solver_options(ui);
             });
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -85,6 +555,30 @@ impl eframe::App for MaunaKea {
                 ui.text_edit_singleline(&mut self.options.info_path);
             });
             ui.text_edit_multiline(&mut self.options.checkpoints);
+            ui.horizontal(|ui| {
+                ui.label("Pickups to collect first: ");
+                ui.text_edit_singleline(&mut self.options.pickups);
+                if ui.button("Load Pickups").clicked() {
+                    let (level, _) = Level::load(&self.options.info_path);
+                    self.pickup_list = level
+                        .pickups
+                        .iter()
+                        .enumerate()
+                        .map(|(i, p)| format!("{i}: {:?} at {}, {}", p.kind, p.pos.x, p.pos.y))
+                        .collect();
+                }
+            });
+            for pickup in self.pickup_list.iter() {
+                ui.label(pickup);
+            }
+            ui.horizontal(|ui| {
+                ui.label("TAS to start from (optional): ");
+                ui.text_edit_singleline(&mut self.options.tas_path);
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.options.save_state, "Save GA state to: ");
+                ui.text_edit_singleline(&mut self.options.state_path);
+            });
             if ui.button("Set Custom Info Template").clicked() {
                 let client = reqwest::blocking::Client::new();
                 let resp1 = client.get("http://localhost:32270/tas/custominfo").send();
@@ -131,10 +625,109 @@ impl eframe::App for MaunaKea {
                     Err(_e) => println!("didnt work! :("),
                 }
             }
-            if ui.button("Run (INCOMPLETE)").clicked() {
-                println!("{}", "Running!".bright_green());
-                let (level, player) = Level::load(&self.options.info_path);
-                algorithm::run_alg(level, player, &self.options.checkpoints);
+            if let Some(run) = &self.run {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    if ui.button("Cancel").clicked() {
+                        run.cancel();
+                    }
+                });
+            } else {
+                ui.horizontal(|ui| {
+                    if ui.button("Run (INCOMPLETE)").clicked() {
+                        let tas = if self.options.tas_path.is_empty() {
+                            Ok(String::new())
+                        } else {
+                            std::fs::read_to_string(&self.options.tas_path)
+                        };
+                        let tas = match tas {
+                            Ok(tas) => tas,
+                            Err(e) => {
+                                println!("{}", format!("Couldn't read the TAS: {e}").red());
+                                return;
+                            }
+                        };
+                        let info = match RunInfo::new(
+                            &self.options.info_path,
+                            &self.options.checkpoints,
+                            &self.options.pickups,
+                        ) {
+                            Ok(info) => info,
+                            Err(e) => {
+                                println!("{}", e.to_string().red());
+                                return;
+                            }
+                        };
+                        println!("{}", "Running!".bright_green());
+                        let (level, mut player) = Level::load(&self.options.info_path);
+                        if self.options.limit_refills {
+                            player.level_state.refills = Some(self.options.refills);
+                        }
+                        self.progress = None;
+                        self.shortened = None;
+                        self.front.clear();
+                        self.run = Some(algorithm::spawn_run(
+                            level,
+                            player,
+                            info,
+                            tas,
+                            self.options.solver.clone(),
+                            self.save_path(),
+                        ));
+                    }
+                    if ui.button("Resume").clicked() {
+                        match SolverState::load(&self.options.state_path) {
+                            Ok(state) => {
+                                println!("{}", "Resuming!".bright_green());
+                                // the saved player already has the refills it started with
+                                let (level, _) = Level::load(&state.info_path);
+                                self.progress = None;
+                                self.shortened = None;
+                                self.front.clear();
+                                self.run =
+                                    Some(algorithm::spawn_resume(level, state, self.save_path()));
+                            }
+                            Err(e) => println!("{}", e.to_string().red()),
+                        }
+                    }
+                });
+            }
+            if let Some(progress) = &self.progress {
+                ui.label(format!(
+                    "Generation {}, {} frames, best fitness {:.3}, {} checkpoints hit",
+                    progress.generation,
+                    progress.horizon,
+                    progress.best_fitness,
+                    progress.checkpoint_index
+                ));
+            }
+            if let Some(frames) = self.shortened {
+                ui.label(format!("Shortening saved {frames} frames"));
+            }
+            if let Some(breakdown) = &self.fitness {
+                ui.label(format!("Fitness: {:.3}", breakdown.total()));
+                for (name, value) in breakdown.terms() {
+                    ui.label(format!("    {name}: {value:.3}"));
+                }
+            }
+            if !self.front.is_empty() {
+                ui.separator();
+                ui.label("Pareto front:");
+                for solution in self.front.iter() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "{} frames, exit speed {:.3}, exit subpixel {:.3}",
+                            solution.frames, solution.exit_speed, solution.exit_subpixel
+                        ));
+                        if ui.button("Copy").clicked() {
+                            let copied = Clipboard::new()
+                                .and_then(|mut clipboard| clipboard.set_text(&solution.inputs));
+                            if let Err(e) = copied {
+                                println!("{}", e.to_string().red());
+                            }
+                        }
+                    });
+                }
             }
         });
     }
@@ -3,6 +3,7 @@ use colored::Colorize;
 use image::{ImageBuffer, Rgb, RgbImage};
 use regex::Regex;
 use rstar::RTree;
+use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
 use std::io::stdout;
@@ -11,8 +12,12 @@ use std::io::Write;
 use crate::colliders::Circle;
 use crate::colliders::Direction;
 use crate::colliders::{Collider, Rect};
+use crate::entities::{
+    Pickup, PickupKind, Puffer, PufferState, SpringOrientation, VelocityEntity, VelocityKind,
+};
 use crate::player::{MovementPrecomputer, Player};
 use crate::point::Point;
+use crate::timing::EngineTiming;
 
 #[derive(Debug, Default)]
 pub struct Level {
@@ -24,6 +29,22 @@ pub struct Level {
     temp_death: Vec<Collider>,
     pub static_death: Vec<bv::BitVec>,
     pub static_solids: Vec<bv::BitVec>,
+    pub max_dashes: u32,
+    pub puffers: Vec<Puffer>,
+    pub velocity_entities: Vec<VelocityEntity>,
+    pub pickups: Vec<Pickup>,
+}
+
+/// The parts of a level that change as the player moves through it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LevelState {
+    /// How many more times dashes can be refilled, or `None` if there is no limit.
+    pub refills: Option<u32>,
+    pub puffers: Vec<PufferState>,
+    /// Time until each of the level's velocity entities can be used again.
+    pub velocity_cooldowns: Vec<f32>,
+    /// Time until each refill in the level's pickups respawns.
+    pub refill_cooldowns: Vec<f32>,
 }
 
 impl Level {
@@ -44,11 +65,42 @@ impl Level {
         level.load_bounds(caps.get(7).unwrap().as_str().to_owned());
         level.load_solids(caps.get(8).unwrap().as_str().to_owned());
         level.load_spinners(caps.get(5).unwrap().as_str().to_owned());
+        if let Some(puffers) = Self::get_field(data, "PufferPos") {
+            level.load_puffers(puffers);
+        }
+        if let Some(bumpers) = Self::get_field(data, "BumperPos") {
+            level.load_bumpers(bumpers);
+        }
+        if let (Some(springs), Some(orientations)) = (
+            Self::get_field(data, "SpringPos"),
+            Self::get_field(data, "SpringOrientation"),
+        ) {
+            level.load_springs(springs, orientations);
+        }
+        for (field, kind) in [
+            ("StrawberryPos", PickupKind::Strawberry),
+            ("KeyPos", PickupKind::Key),
+            ("RefillPos", PickupKind::Refill),
+        ] {
+            if let Some(pickups) = Self::get_field(data, field) {
+                level.load_pickups(pickups, kind);
+            }
+        }
         level.solids = RTree::bulk_load(level.temp_solids.clone());
         level.death = RTree::bulk_load(level.temp_death.clone());
         level.precomputed = MovementPrecomputer::new(&level.solids, &level.death, level.bounds);
         level.temp_solids = vec![];
         level.temp_death = vec![];
+        let mut player = Self::load_player(
+            caps.get(2).unwrap().as_str().to_owned(),
+            caps.get(3).unwrap().as_str().to_owned(),
+            caps.get(4).unwrap().as_str().to_owned(),
+        );
+        (player.dashes, level.max_dashes) = Self::load_dashes(data);
+        player.timing = Self::load_timing(data);
+        player.level_state.puffers = vec![PufferState::Idle; level.puffers.len()];
+        player.level_state.velocity_cooldowns = vec![0f32; level.velocity_entities.len()];
+        player.level_state.refill_cooldowns = vec![0f32; level.pickups.len()];
         /*let mut img = ImageBuffer::new(
             self.static_death[0].len() as u32,
             self.static_death.len() as u32,
@@ -66,14 +118,7 @@ impl Level {
             }
         }
         img.save("testimg.png").unwrap();*/
-        (
-            level,
-            Self::load_player(
-                caps.get(2).unwrap().as_str().to_owned(),
-                caps.get(3).unwrap().as_str().to_owned(),
-                caps.get(4).unwrap().as_str().to_owned(),
-            ),
-        )
+        (level, player)
     }
 
     #[inline]
@@ -226,6 +271,51 @@ impl Level {
         }
     }
 
+    /// Finds the value of a template field that isn't part of the main regex.
+    fn get_field<'a>(data: &'a str, name: &str) -> Option<&'a str> {
+        Regex::new(&format!(r"\b{name}: ([^:]*) \w+:"))
+            .ok()?
+            .captures(data)?
+            .get(1)
+            .map(|m| m.as_str())
+    }
+
+    fn load_puffers(&mut self, data: &str) {
+        for p in data.split('[').skip(1) {
+            self.puffers.push(Puffer::new(Self::get_pair(p)));
+        }
+    }
+
+    fn load_bumpers(&mut self, data: &str) {
+        for p in data.split('[').skip(1) {
+            self.velocity_entities
+                .push(VelocityEntity::new(VelocityKind::Bumper, Self::get_pair(p)));
+        }
+    }
+
+    fn load_springs(&mut self, positions: &str, orientations: &str) {
+        let Ok(re) = Regex::new(r"Floor|WallLeft|WallRight") else {
+            return;
+        };
+        let orientations = re.find_iter(orientations).map(|m| match m.as_str() {
+            "Floor" => SpringOrientation::Floor,
+            "WallLeft" => SpringOrientation::WallLeft,
+            _ => SpringOrientation::WallRight,
+        });
+        for (p, orientation) in positions.split('[').skip(1).zip(orientations) {
+            self.velocity_entities.push(VelocityEntity::new(
+                VelocityKind::Spring(orientation),
+                Self::get_pair(p),
+            ));
+        }
+    }
+
+    fn load_pickups(&mut self, data: &str, kind: PickupKind) {
+        for p in data.split('[').skip(1) {
+            self.pickups.push(Pickup::new(kind, Self::get_pair(p)));
+        }
+    }
+
     fn load_solids(&mut self, data: String) {
         let rows = data.split(' ').collect::<Vec<_>>();
         for (y, row) in rows.iter().enumerate() {
@@ -245,6 +335,55 @@ impl Level {
         }
     }
 
+    // NOTE: older templates dont have these, so they fall back to vanilla's single dash
+    fn load_dashes(data: &str) -> (u32, u32) {
+        let caps = Regex::new(r"\bDashes: (\d+) MaxDashes: (\d+)")
+            .ok()
+            .and_then(|re| re.captures(data));
+        match caps {
+            Some(caps) => (caps[1].parse().unwrap_or(1), caps[2].parse().unwrap_or(1)),
+            None => (1, 1),
+        }
+    }
+
+    fn load_timing(data: &str) -> EngineTiming {
+        let caps = Regex::new(r"TimeRate: (-?\d+\.?\d*) FreezeTimer: (-?\d+\.?\d*)")
+            .ok()
+            .and_then(|re| re.captures(data));
+        match caps {
+            Some(caps) => EngineTiming::new(
+                caps[1].parse().unwrap_or(1f32),
+                caps[2].parse().unwrap_or(0f32),
+            ),
+            None => EngineTiming::default(),
+        }
+    }
+
+    /// Nothing but water inside `bounds`.
+    #[cfg(test)]
+    pub fn open_water(bounds: Rect) -> Level {
+        Level {
+            bounds,
+            precomputed: MovementPrecomputer::new(
+                &RTree::bulk_load(vec![]),
+                &RTree::bulk_load(vec![]),
+                bounds,
+            ),
+            ..Default::default()
+        }
+    }
+
+    /// Open water around the origin with the player still in the middle of it and a checkpoint
+    /// a little to the right.
+    #[cfg(test)]
+    pub fn test_course() -> (Level, Player, Rect) {
+        (
+            Self::open_water(Rect::new_xywh(-64f32, -64f32, 128f32, 128f32)),
+            Player::new(Point::new(0f32, 0f32), Point::new(0f32, 0f32)),
+            Rect::new_xywh(10f32, -16f32, 4f32, 32f32),
+        )
+    }
+
     fn load_player(position: String, position_remainder: String, speed: String) -> Player {
         let pair1 = Self::get_pair(&position);
         let pair2 = Self::get_pair(&position_remainder);
@@ -2,10 +2,12 @@
 
 mod algorithm;
 mod colliders;
+mod entities;
 mod gui;
 mod level;
 mod player;
 mod point;
+mod timing;
 
 use colored::Colorize;
 
@@ -1,12 +1,36 @@
 use rayon::prelude::*;
 use rstar::RTree;
+use serde::{Deserialize, Serialize};
 
 use crate::colliders::{Collider, Direction, Rect};
-use crate::level::Level;
+use crate::entities::{
+    PickupKind, PufferState, SpringOrientation, VelocityKind, SIDE_BOUNCE_SPEED,
+    SIDE_BOUNCE_UP_SPEED, SUPER_BOUNCE_SPEED,
+};
+use crate::level::{Level, LevelState};
 use crate::point::Point;
+use crate::timing::EngineTiming;
 
-const DELTATIME: f32 = 0.0166667;
-const DELTATIME_RECIP: f32 = 1f32 / 0.0166667;
+/// Genes at or above this press dash on that frame, aiming at `gene - DASH_OFFSET`.
+pub const DASH_OFFSET: f64 = 360f64;
+
+const MOVE_DEADZONE: f64 = 0.3f64;
+const SWIM_ACCEL: f32 = 600f32;
+const SWIM_REDUCE: f32 = 400f32;
+pub(crate) const SWIM_MAX_X: f32 = 60f32;
+pub(crate) const SWIM_MAX_Y: f32 = 80f32;
+const DASH_SPEED: f32 = 240f32;
+const END_DASH_SPEED: f32 = 160f32;
+const END_DASH_UP_MULT: f32 = 0.75f32;
+const DASH_TIME: f32 = 0.15f32;
+const DASH_COOLDOWN: f32 = 0.2f32;
+const DASH_REFILL_COOLDOWN: f32 = 0.1f32;
+const EXPLODE_LAUNCH_SPEED: f32 = 280f32;
+const LAUNCHED_MIN_SPEED: f32 = 220f32;
+const BOUNCE_SPEED: f32 = -140f32;
+const MAX_FALL: f32 = 160f32;
+const GRAVITY: f32 = 900f32;
+const RUN_ACCEL: f32 = 1000f32;
 
 #[derive(Debug, Default)]
 pub struct MovementPrecomputer {
@@ -24,6 +48,15 @@ impl MovementPrecomputer {
         }
     }
 
+    /// Whether this was never actually built, like in a default `Level`.
+    pub fn is_empty(&self) -> bool {
+        self.solids.is_empty()
+    }
+
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+
     #[inline]
     fn get_index(&self, position: &Point, direction: Direction) -> usize {
         let dir = match direction {
@@ -144,7 +177,7 @@ pub enum FrameResult {
     Death,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Player {
     pub speed: Point,
     pub retained: f32,
@@ -152,6 +185,18 @@ pub struct Player {
     pub alive: bool,
     pub hurtbox: Collider,
     pub hitbox: Collider,
+    pub dashes: u32,
+    pub dash_held: bool,
+    pub dash_timer: f32,
+    pub dash_cooldown_timer: f32,
+    pub dash_refill_cooldown_timer: f32,
+    pub dash_dir: Point,
+    pub move_x: i32,
+    pub launched: bool,
+    pub explode_launch_boost_timer: f32,
+    pub explode_launch_boost_speed: f32,
+    pub level_state: LevelState,
+    pub timing: EngineTiming,
 }
 
 impl Player {
@@ -169,6 +214,18 @@ impl Player {
                 Point::new(position.x - 4f32, position.y - 11f32),
                 Point::new(position.x + 3f32, position.y - 1f32),
             )),
+            dashes: 0,
+            dash_held: false,
+            dash_timer: 0f32,
+            dash_cooldown_timer: 0f32,
+            dash_refill_cooldown_timer: 0f32,
+            dash_dir: Point::default(),
+            move_x: 0,
+            launched: false,
+            explode_launch_boost_timer: 0f32,
+            explode_launch_boost_speed: 0f32,
+            level_state: LevelState::default(),
+            timing: EngineTiming::default(),
         }
     }
 
@@ -181,22 +238,308 @@ impl Player {
         Point::new(ul.x + 4f32, ul.y + 11f32)
     }
 
-    // TODO: add in stuff for when speed is outside octagon and should be pulled back to it
-    // TODO: make speed capping actually work how its meant to
+    #[inline]
+    pub fn dashing(&self) -> bool {
+        self.dash_timer > 0f32
+    }
+
     // TODO: water surface bs
-    pub fn speed_calc(&mut self, angle: f64, level: &Level) {
+    pub fn speed_calc(&mut self, gene: f64, level: &Level) {
+        let dash_gene = gene >= DASH_OFFSET;
+        let angle = if dash_gene { gene - DASH_OFFSET } else { gene };
+        let dash_pressed = dash_gene && !self.dash_held;
+        self.dash_held = dash_gene;
+        let aim_x = angle.to_radians().sin();
+        self.move_x = if aim_x.abs() < MOVE_DEADZONE {
+            0
+        } else {
+            aim_x.signum() as i32
+        };
+        let delta_time = self.timing.delta_time();
+        if self.explode_launch_boost_timer > 0f32 {
+            if self.move_x as f32 == self.explode_launch_boost_speed.signum() {
+                self.speed.x = self.explode_launch_boost_speed;
+                self.explode_launch_boost_timer = 0f32;
+            } else {
+                self.explode_launch_boost_timer -= delta_time;
+            }
+        }
+        self.dash_cooldown_timer = f32::max(self.dash_cooldown_timer - delta_time, 0f32);
+        self.dash_refill_cooldown_timer =
+            f32::max(self.dash_refill_cooldown_timer - delta_time, 0f32);
+        if !self.dashing() && self.dash_refill_cooldown_timer <= 0f32 {
+            self.refill_dash(level);
+        }
+        if dash_pressed && self.dashes > 0 && self.dash_cooldown_timer <= 0f32 {
+            self.start_dash(angle);
+            return;
+        }
+        if self.dashing() {
+            self.dash_timer -= delta_time;
+            if !self.dashing() {
+                self.end_dash();
+            }
+            return;
+        }
+        if self.launched {
+            self.launch_update();
+            return;
+        }
+        self.swim(angle, level);
+    }
+
+    /// Approaches `target` by at most `max_move`, like `Calc.Approach`.
+    #[inline]
+    fn approach(val: f32, target: f32, max_move: f32) -> f32 {
+        if val > target {
+            f32::max(val - max_move, target)
+        } else {
+            f32::min(val + max_move, target)
+        }
+    }
+
+    /// `Player.ExplodeLaunch`, used by anything that explodes the player away from `from`.
+    pub fn explode_launch(&mut self, level: &Level, from: Point, snap_up: bool, sides_only: bool) {
+        self.timing.freeze(0.1f32);
+        let center = match self.hitbox.rect() {
+            Some(rect) => rect,
+            None => unreachable!(),
+        }
+        .center();
+        let mut dir = if center == from {
+            Point::new(0f32, -1f32)
+        } else {
+            (center - from).normalize()
+        };
+        if snap_up && dir.y <= -0.7f32 {
+            dir = Point::new(0f32, -1f32);
+        } else if (-0.55f32..=0.65f32).contains(&dir.y) || (sides_only && dir.x != 0f32) {
+            dir = Point::new(dir.x.signum(), 0f32);
+        }
+        self.speed = dir * EXPLODE_LAUNCH_SPEED;
+        if self.speed.y <= 50f32 {
+            self.speed.y = f32::min(-150f32, self.speed.y);
+        }
+        if self.speed.x != 0f32 {
+            if self.move_x as f32 == self.speed.x.signum() {
+                self.explode_launch_boost_timer = 0f32;
+                self.speed.x *= 1.2f32;
+            } else {
+                self.explode_launch_boost_timer = 0.01f32;
+                self.explode_launch_boost_speed = self.speed.x * 1.2f32;
+            }
+        }
+        self.dashes = u32::max(self.dashes, level.max_dashes);
+        self.dash_timer = 0f32;
+        self.dash_cooldown_timer = DASH_COOLDOWN;
+        self.retained = 0f32;
+        self.retained_timer = 0;
+        self.launched = true;
+    }
+
+    fn launch_update(&mut self) {
+        let delta_time = self.timing.delta_time();
+        let gravity_mult = if self.speed.y < 0f32 { 0.5f32 } else { 0.25f32 };
+        self.speed.y = Self::approach(self.speed.y, MAX_FALL, GRAVITY * gravity_mult * delta_time);
+        self.speed.x = Self::approach(self.speed.x, 0f32, RUN_ACCEL * 0.2f32 * delta_time);
+        if self.speed.magnitude() < LAUNCHED_MIN_SPEED {
+            self.launched = false;
+            if self.speed.y > 0f32 {
+                self.speed.y *= 0.5f32;
+            }
+        }
+    }
+
+    pub fn update_entities(&mut self, level: &Level) {
+        self.update_puffers(level);
+        self.update_velocity_entities(level);
+        self.update_refills(level);
+    }
+
+    /// Refill crystals give dashes back when touched, as long as the player is missing some.
+    pub fn update_refills(&mut self, level: &Level) {
+        let delta_time = self.timing.delta_time();
+        for (i, pickup) in level.pickups.iter().enumerate() {
+            if pickup.kind != PickupKind::Refill {
+                continue;
+            }
+            let cooldown = &mut self.level_state.refill_cooldowns[i];
+            if *cooldown > 0f32 {
+                *cooldown = f32::max(*cooldown - delta_time, 0f32);
+                continue;
+            }
+            if self.dashes < level.max_dashes
+                && self
+                    .hitbox
+                    .collide_check(&Collider::Rectangular(pickup.hitbox()))
+            {
+                self.dashes = level.max_dashes;
+                *cooldown = pickup.respawn_time().unwrap_or_default();
+            }
+        }
+    }
+
+    /// `Player.SuperBounce`, from floor springs.
+    fn super_bounce(&mut self, level: &Level) {
+        self.dashes = u32::max(self.dashes, level.max_dashes);
+        self.speed.y = SUPER_BOUNCE_SPEED;
+        self.launched = false;
+    }
+
+    /// `Player.SideBounce`, from wall springs. Doesn't slow the player down if they're already faster.
+    fn side_bounce(&mut self, level: &Level, dir: f32) -> bool {
+        if self.speed.x.abs() > SIDE_BOUNCE_SPEED && self.speed.x.signum() == dir {
+            return false;
+        }
+        self.dashes = u32::max(self.dashes, level.max_dashes);
+        self.speed = Point::new(SIDE_BOUNCE_SPEED * dir, SIDE_BOUNCE_UP_SPEED);
+        self.launched = false;
+        true
+    }
+
+    /// Runs bumpers and springs for one frame, setting the player's speed if they're touched.
+    pub fn update_velocity_entities(&mut self, level: &Level) {
+        let delta_time = self.timing.delta_time();
+        for (i, entity) in level.velocity_entities.iter().enumerate() {
+            let cooldown = &mut self.level_state.velocity_cooldowns[i];
+            if *cooldown > 0f32 {
+                *cooldown = f32::max(*cooldown - delta_time, 0f32);
+                continue;
+            }
+            if !self.hitbox.collide_check(&entity.collider()) {
+                continue;
+            }
+            let used = match entity.kind {
+                VelocityKind::Bumper => {
+                    self.explode_launch(level, entity.pos, false, false);
+                    true
+                }
+                VelocityKind::Spring(SpringOrientation::Floor) => {
+                    let used = self.speed.y >= 0f32;
+                    if used {
+                        self.super_bounce(level);
+                    }
+                    used
+                }
+                VelocityKind::Spring(SpringOrientation::WallLeft) => self.side_bounce(level, 1f32),
+                VelocityKind::Spring(SpringOrientation::WallRight) => {
+                    self.side_bounce(level, -1f32)
+                }
+            };
+            if used {
+                self.level_state.velocity_cooldowns[i] = entity.respawn_time();
+            }
+        }
+    }
+
+    /// Runs every puffer for one frame, launching or bouncing the player off of them.
+    pub fn update_puffers(&mut self, level: &Level) {
+        let delta_time = self.timing.delta_time();
+        for (i, puffer) in level.puffers.iter().enumerate() {
+            let state = self.level_state.puffers[i];
+            let next = puffer.update(state, &self.hitbox, delta_time);
+            match (state, next) {
+                (PufferState::Idle | PufferState::Alert, PufferState::Exploding)
+                    if self.hitbox.collide_check(&puffer.push_radius()) =>
+                {
+                    self.explode_launch(level, puffer.pos, false, true);
+                }
+                (PufferState::Idle | PufferState::Alert, PufferState::Cooldown(_)) => {
+                    self.speed.y = BOUNCE_SPEED;
+                    self.launched = false;
+                }
+                _ => (),
+            }
+            self.level_state.puffers[i] = next;
+        }
+    }
+
+    /// Water refills dashes like the ground does, unless the level has run out of refills.
+    fn refill_dash(&mut self, level: &Level) {
+        if self.dashes >= level.max_dashes {
+            return;
+        }
+        match self.level_state.refills {
+            Some(0) => return,
+            Some(ref mut refills) => *refills -= 1,
+            None => (),
+        }
+        self.dashes = level.max_dashes;
+    }
+
+    fn start_dash(&mut self, angle: f64) {
+        // without 360 dashing the aim vector gets snapped to the 8 directions
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        self.dash_dir = match (f64::round(angle / 45f64) as i32).rem_euclid(8) {
+            0 => Point::new(0f32, -1f32),
+            1 => Point::new(diagonal, -diagonal),
+            2 => Point::new(1f32, 0f32),
+            3 => Point::new(diagonal, diagonal),
+            4 => Point::new(0f32, 1f32),
+            5 => Point::new(-diagonal, diagonal),
+            6 => Point::new(-1f32, 0f32),
+            _ => Point::new(-diagonal, -diagonal),
+        };
+        self.dashes -= 1;
+        self.dash_timer = DASH_TIME;
+        self.dash_cooldown_timer = DASH_COOLDOWN;
+        self.dash_refill_cooldown_timer = DASH_REFILL_COOLDOWN;
+        if self.timing.time_rate > 0.25f32 {
+            self.timing.freeze(0.05f32);
+        }
+        let mut speed = self.dash_dir * DASH_SPEED;
+        if self.speed.x.signum() == speed.x.signum() && self.speed.x.abs() > speed.x.abs() {
+            speed.x = self.speed.x;
+        }
+        self.speed = speed;
+        self.retained = 0f32;
+        self.retained_timer = 0;
+    }
+
+    fn end_dash(&mut self) {
+        self.dash_timer = 0f32;
+        if self.dash_dir.y <= 0f32 {
+            self.speed = self.dash_dir * END_DASH_SPEED;
+        }
+        if self.speed.y < 0f32 {
+            self.speed.y *= END_DASH_UP_MULT;
+        }
+        // dashes always end in the normal state, which goes straight back into swimming
+        if self.speed.y > 0f32 {
+            self.speed.y *= 0.5f32;
+        }
+    }
+
+    /// The speed swimming at `angle` accelerates towards.
+    pub(crate) fn swim_target(angle: f64) -> Point {
         let truncated = f64::round(angle * 1000f64) / 1000f64;
         let adjusted = Point::new(
             truncated.to_radians().sin() as f32,
             -truncated.to_radians().cos() as f32,
         );
-        self.retained_timer -= 1;
-        let target = Point::new(60f32 * adjusted.x, 80f32 * adjusted.y);
-        if f32::abs(target.x - self.speed.x) < 10f32 {
-            self.speed.x = target.x;
+        Point::new(SWIM_MAX_X * adjusted.x, SWIM_MAX_Y * adjusted.y)
+    }
+
+    /// One axis of swimming towards `target`. Swimming above `max` in the held direction only
+    /// slows down at `SWIM_REDUCE`.
+    pub(crate) fn swim_axis(speed: f32, target: f32, max: f32, delta_time: f32) -> f32 {
+        let accel = if speed.abs() > max && speed.signum() == target.signum() {
+            SWIM_REDUCE * delta_time
         } else {
-            self.speed.x += f32::clamp(target.x - self.speed.x, -10f32, 10f32);
+            SWIM_ACCEL * delta_time
+        };
+        if f32::abs(target - speed) < accel {
+            target
+        } else {
+            speed + f32::clamp(target - speed, -accel, accel)
         }
+    }
+
+    fn swim(&mut self, angle: f64, level: &Level) {
+        let target = Self::swim_target(angle);
+        let delta_time = self.timing.delta_time();
+        self.retained_timer -= 1;
+        self.speed.x = Self::swim_axis(self.speed.x, target.x, SWIM_MAX_X, delta_time);
         if self.speed.x.signum() == self.retained.signum()
             && self.retained_timer > 0
             && level.precomputed.get_solid(
@@ -212,23 +555,13 @@ impl Player {
             self.retained = 0f32;
             self.retained_timer = 0;
         }
-        self.speed.x = self.speed.x.clamp(-60f32, 60f32);
-        if f32::abs(target.y - self.speed.y) < 10f32 {
-            self.speed.y = target.y;
-        } else {
-            self.speed.y += f32::clamp(target.y - self.speed.y, -10f32, 10f32);
-        }
-        self.speed.y = self.speed.y.clamp(-80f32, 80f32);
-    }
-
-    pub fn speed_calc_restricted(&mut self) {
-        todo!()
+        self.speed.y = Self::swim_axis(self.speed.y, target.y, SWIM_MAX_Y, delta_time);
     }
 
     fn move_in_direction(&mut self, level: &Level, speed: f32, dir: Direction) -> bool {
         let pos = self.pos();
         let pos_r = pos.round();
-        let pixels_f = speed * DELTATIME;
+        let pixels_f = speed * self.timing.delta_time();
         let pixels_i = match dir {
             Direction::Left | Direction::Right => {
                 if pos_r.x == f32::round(pos.x + pixels_f.fract()) {
@@ -260,10 +593,8 @@ impl Player {
             hit = true;
         }
         let (x, y) = match dir {
-            Direction::Left => (to_move * DELTATIME_RECIP, 0f32),
-            Direction::Up => (0f32, to_move * DELTATIME_RECIP),
-            Direction::Right => (to_move * DELTATIME_RECIP, 0f32),
-            Direction::Down => (0f32, to_move * DELTATIME_RECIP),
+            Direction::Left | Direction::Right => (to_move, 0f32),
+            Direction::Up | Direction::Down => (0f32, to_move),
         };
         self.hitbox.move_collider(x, y);
         self.hurtbox.move_collider(x, y);
@@ -364,6 +695,8 @@ impl Player {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::entities::{Puffer, VelocityEntity};
+    use crate::timing::RAW_DELTA_TIME;
 
     #[test]
     fn precompute_test_death() {
@@ -440,7 +773,7 @@ mod tests {
         let mut player = Player::new(Point::new(0f32, 0f32), Point::new(0f32, 0f32));
         for be_true in 0..=3 {
             for amount in 0..=512 {
-                let to_move = amount as f32 / 4f32 * DELTATIME_RECIP;
+                let to_move = amount as f32 / 4f32 / RAW_DELTA_TIME;
                 match be_true {
                     0 => player.speed = Point::new(-to_move, 0f32),
                     1 => player.speed = Point::new(0f32, -to_move),
@@ -472,4 +805,71 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn dash_test() {
+        let mut level = Level::default();
+        level.max_dashes = 1;
+        level.precomputed = MovementPrecomputer::new(
+            &RTree::bulk_load(vec![]),
+            &RTree::bulk_load(vec![]),
+            Rect::new_xywh(-64f32, -64f32, 128f32, 128f32),
+        );
+        let mut player = Player::new(Point::new(0f32, 0f32), Point::new(0f32, 0f32));
+        player.dashes = 1;
+        player.level_state.refills = Some(0);
+        player.speed_calc(DASH_OFFSET + 80f64, &level);
+        assert_eq!(player.speed, Point::new(240f32, 0f32));
+        assert_eq!(player.dashes, 0);
+        // holding dash doesnt dash again, and the dash runs out into end dash speed
+        for _ in 0..9 {
+            player.speed_calc(DASH_OFFSET + 80f64, &level);
+        }
+        assert!(!player.dashing());
+        assert_eq!(player.speed, Point::new(160f32, 0f32));
+        // over the swim cap the speed only comes down at the reduce rate
+        player.speed_calc(90f64, &level);
+        assert!(player.speed.x > 60f32 && player.speed.x < 160f32);
+        assert_eq!(player.dashes, 0);
+    }
+
+    #[test]
+    fn puffer_launch_test() {
+        let mut level = Level::default();
+        level.puffers = vec![Puffer::new(Point::new(0f32, 0f32))];
+        let mut player = Player::new(Point::new(0f32, 0f32), Point::new(20f32, 10f32));
+        player.level_state.puffers = vec![PufferState::Idle];
+        player.update_puffers(&level);
+        assert_eq!(player.level_state.puffers[0], PufferState::Exploding);
+        assert_eq!(player.speed, Point::new(280f32, -150f32));
+        assert!(player.launched);
+        // the launch freezes the game, and the puffer stays gone for a while afterwards
+        assert!(!player.timing.tick());
+        player.update_puffers(&level);
+        assert!(matches!(
+            player.level_state.puffers[0],
+            PufferState::Cooldown(_)
+        ));
+    }
+
+    #[test]
+    fn velocity_entity_test() {
+        let mut level = Level::default();
+        level.velocity_entities = vec![
+            VelocityEntity::new(
+                VelocityKind::Spring(SpringOrientation::WallLeft),
+                Point::new(0f32, 0f32),
+            ),
+            VelocityEntity::new(VelocityKind::Bumper, Point::new(200f32, 0f32)),
+        ];
+        let mut player = Player::new(Point::new(-30f32, 0f32), Point::new(5f32, 4f32));
+        player.level_state.velocity_cooldowns = vec![0f32; 2];
+        player.update_entities(&level);
+        assert_eq!(player.speed, Point::new(240f32, -140f32));
+        let mut player = Player::new(Point::new(0f32, 0f32), Point::new(200.5f32, -5f32));
+        player.level_state.velocity_cooldowns = vec![0f32; 2];
+        player.update_entities(&level);
+        assert_eq!(player.speed, Point::new(0f32, -280f32));
+        assert!(player.level_state.velocity_cooldowns[1] > 0f32);
+    }
 }
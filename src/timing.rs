@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+/// `Engine.RawDeltaTime`. XNA targets 166667 ticks a frame, so this is exactly
+/// `(float)0.0166667` rather than an approximation of 1/60.
+pub const RAW_DELTA_TIME: f32 = 0.0166667;
+
+/// How much game time each frame advances by, mirroring `Engine.TimeRate` and `Engine.FreezeTimer`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct EngineTiming {
+    pub time_rate: f32,
+    pub freeze_timer: f32,
+}
+
+impl Default for EngineTiming {
+    fn default() -> Self {
+        Self {
+            time_rate: 1f32,
+            freeze_timer: 0f32,
+        }
+    }
+}
+
+impl EngineTiming {
+    pub fn new(time_rate: f32, freeze_timer: f32) -> Self {
+        Self {
+            time_rate,
+            freeze_timer,
+        }
+    }
+
+    /// `Engine.DeltaTime`
+    #[inline]
+    pub fn delta_time(&self) -> f32 {
+        RAW_DELTA_TIME * self.time_rate
+    }
+
+    /// `Celeste.Freeze`, never shortens a freeze that is already running
+    pub fn freeze(&mut self, time: f32) {
+        self.freeze_timer = f32::max(self.freeze_timer, time);
+    }
+
+    /// Steps the engine forward one frame, returning whether the scene actually updated.
+    /// Frozen frames still use up an input frame, they just dont simulate anything.
+    pub fn tick(&mut self) -> bool {
+        if self.freeze_timer > 0f32 {
+            self.freeze_timer = f32::max(self.freeze_timer - RAW_DELTA_TIME, 0f32);
+            false
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freeze_test() {
+        let mut timing = EngineTiming::new(0.5f32, 0f32);
+        assert_eq!(timing.delta_time(), RAW_DELTA_TIME * 0.5f32);
+        timing.freeze(0.05f32);
+        timing.freeze(0.01f32);
+        let frozen = (0..10).take_while(|_| !timing.tick()).count();
+        assert_eq!(frozen, 3);
+    }
+}